@@ -0,0 +1,57 @@
+use async_graphql::SimpleObject;
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+
+/// A notice posted to everyone in a room.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject)]
+#[sea_orm(table_name = "announcement")]
+#[graphql(name = "Announcement")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub room_id: i32,
+    /// `None` once the author's account is gone.
+    pub author_id: Option<i32>,
+
+    pub title: String,
+    /// Markdown, rendered by the client.
+    pub body: String,
+
+    /// Pinned announcements are listed first.
+    pub pinned_at: Option<NaiveDateTime>,
+    /// Students only see the announcement from this point on.
+    pub publish_at: NaiveDateTime,
+
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+
+    /// When the current user read it, if they did.
+    #[sea_orm(ignore)]
+    pub read_at: Option<NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::room::Entity",
+        from = "Column::RoomId",
+        to = "super::room::Column::Id"
+    )]
+    Room,
+    #[sea_orm(has_many = "super::announcement_read::Entity")]
+    AnnouncementRead,
+}
+
+impl Related<super::room::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Room.def()
+    }
+}
+
+impl Related<super::announcement_read::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AnnouncementRead.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,45 @@
+use async_graphql::SimpleObject;
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+
+/// Records that a member has read an announcement.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject)]
+#[sea_orm(table_name = "announcement_read")]
+#[graphql(name = "AnnouncementRead")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub announcement_id: i32,
+    pub user_id: i32,
+    pub read_at: NaiveDateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::announcement::Entity",
+        from = "Column::AnnouncementId",
+        to = "super::announcement::Column::Id"
+    )]
+    Announcement,
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::announcement::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Announcement.def()
+    }
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
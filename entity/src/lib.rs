@@ -1,6 +1,18 @@
 pub mod achievment;
+pub mod announcement;
+pub mod announcement_read;
+pub mod login_attempt;
+pub mod personal_access_token;
+pub mod recovery_code;
 pub mod room;
+pub mod room_invite;
+pub mod room_join_request;
+pub mod room_ownership_transfer;
+pub mod session;
+pub mod submission;
 pub mod task;
+pub mod teacher_application;
 pub mod user;
 pub mod user_achievment;
 pub mod user_room;
+pub mod user_token;
@@ -12,14 +12,26 @@ pub struct Model {
 
     #[sea_orm(column_name = "name")]
     pub name: String,
+    pub description: Option<String>,
+    pub subject: Option<String>,
 
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    /// Archived rooms are read-only and left out of room lists.
+    pub archived_at: Option<NaiveDateTime>,
 
     #[sea_orm(ignore)]
     pub tasks: Vec<super::task::Model>,
     #[sea_orm(ignore)]
     pub users: Vec<super::user::Model>,
+    /// Only filled in by room listings.
+    #[sea_orm(ignore)]
+    pub member_count: Option<i64>,
+    #[sea_orm(ignore)]
+    pub task_count: Option<i64>,
+    /// Published announcements the current user has not read yet.
+    #[sea_orm(ignore)]
+    pub unread_announcements: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -28,6 +40,14 @@ pub enum Relation {
     Task,
     #[sea_orm(has_many = "super::user_room::Entity")]
     UserRoom,
+    #[sea_orm(has_many = "super::room_invite::Entity")]
+    RoomInvite,
+    #[sea_orm(has_many = "super::room_join_request::Entity")]
+    RoomJoinRequest,
+    #[sea_orm(has_many = "super::room_ownership_transfer::Entity")]
+    RoomOwnershipTransfer,
+    #[sea_orm(has_many = "super::announcement::Entity")]
+    Announcement,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::Owner",
@@ -49,6 +69,30 @@ impl Related<super::user_room::Entity> for Entity {
     }
 }
 
+impl Related<super::room_invite::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RoomInvite.def()
+    }
+}
+
+impl Related<super::room_join_request::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RoomJoinRequest.def()
+    }
+}
+
+impl Related<super::room_ownership_transfer::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RoomOwnershipTransfer.def()
+    }
+}
+
+impl Related<super::announcement::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Announcement.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
@@ -0,0 +1,41 @@
+use async_graphql::SimpleObject;
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+
+/// A code students enter to join a room. Rotating it revokes the previous one.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject)]
+#[sea_orm(table_name = "room_invite")]
+#[graphql(name = "RoomInvite")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub room_id: i32,
+    pub code: String,
+    pub created_by: i32,
+
+    /// `None` means unlimited.
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::room::Entity",
+        from = "Column::RoomId",
+        to = "super::room::Column::Id"
+    )]
+    Room,
+}
+
+impl Related<super::room::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Room.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,52 @@
+use async_graphql::SimpleObject;
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+
+/// One refresh token. Every refresh rotates the token into a new row of the same `family`.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject)]
+#[sea_orm(table_name = "session")]
+#[graphql(name = "SessionModel")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub user_id: i32,
+
+    pub family: String,
+    #[graphql(visible = false)]
+    pub token_hash: String,
+
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+
+    /// When the family was started by a login.
+    pub created_at: NaiveDateTime,
+    /// When this token was issued, i.e. when the session was last refreshed.
+    #[graphql(name = "lastUsedAt")]
+    pub issued_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Entity {
+    pub fn find_by_token_hash(token_hash: String) -> Select<Entity> {
+        Self::find().filter(Column::TokenHash.eq(token_hash))
+    }
+}
@@ -0,0 +1,70 @@
+use async_graphql::Enum;
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Enum)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum SubmissionStatus {
+    /// Waiting for the teacher.
+    #[sea_orm(string_value = "submitted")]
+    Submitted,
+    /// Sent back so the student can try again.
+    #[sea_orm(string_value = "returned")]
+    Returned,
+    #[sea_orm(string_value = "graded")]
+    Graded,
+}
+
+/// A student's answer to a task.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "submission")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub task_id: i32,
+    pub user_id: i32,
+
+    pub body: String,
+    /// Newline separated URLs.
+    pub attachments: String,
+
+    pub status: SubmissionStatus,
+    /// Starts at 1 and goes up with every resubmission.
+    pub attempt: i32,
+    pub grade: Option<i32>,
+    pub feedback: Option<String>,
+    pub graded_by: Option<i32>,
+
+    pub submitted_at: NaiveDateTime,
+    pub graded_at: Option<NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::task::Entity",
+        from = "Column::TaskId",
+        to = "super::task::Column::Id"
+    )]
+    Task,
+    #[sea_orm(
+        belongs_to = "super::user::Entity",
+        from = "Column::UserId",
+        to = "super::user::Column::Id"
+    )]
+    User,
+}
+
+impl Related<super::task::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Task.def()
+    }
+}
+
+impl Related<super::user::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
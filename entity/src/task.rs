@@ -1,7 +1,17 @@
-use async_graphql::SimpleObject;
+use async_graphql::{Enum, SimpleObject};
 use chrono::NaiveDateTime;
 use sea_orm::entity::prelude::*;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Enum)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum TaskStatus {
+    /// Only the room's staff can see it.
+    #[sea_orm(string_value = "draft")]
+    Draft,
+    #[sea_orm(string_value = "published")]
+    Published,
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject)]
 #[sea_orm(table_name = "task")]
 #[graphql(name = "taskModel")]
@@ -15,6 +25,11 @@ pub struct Model {
 
     pub room_id: i32,
 
+    pub status: TaskStatus,
+    /// Published tasks stay hidden from students until then.
+    pub publish_at: Option<NaiveDateTime>,
+    pub due_at: Option<NaiveDateTime>,
+
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
@@ -27,6 +42,8 @@ pub enum Relation {
         to = "super::room::Column::Id"
     )]
     Room,
+    #[sea_orm(has_many = "super::submission::Entity")]
+    Submission,
 }
 
 impl Related<super::room::Entity> for Entity {
@@ -35,4 +52,10 @@ impl Related<super::room::Entity> for Entity {
     }
 }
 
+impl Related<super::submission::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Submission.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
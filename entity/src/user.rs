@@ -1,6 +1,32 @@
-use async_graphql::SimpleObject;
+use async_graphql::{Enum, SimpleObject};
 use chrono::NaiveDateTime;
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Roles are ordered by privilege, so `role >= Role::Teacher` also admits admins.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    EnumIter,
+    DeriveActiveEnum,
+    Enum,
+    Serialize,
+    Deserialize,
+)]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
+pub enum Role {
+    #[sea_orm(num_value = 0)]
+    Student,
+    #[sea_orm(num_value = 1)]
+    Teacher,
+    #[sea_orm(num_value = 2)]
+    Admin,
+}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject)]
 #[sea_orm(table_name = "user")]
@@ -25,15 +51,23 @@ pub struct Model {
     pub class: String,
     #[sea_orm(column_name = "email")]
     pub email: String,
+    pub email_verified_at: Option<NaiveDateTime>,
 
     #[sea_orm(column_name = "role")]
-    pub role: i32,
+    pub role: Role,
 
     #[graphql(visible = false)]
     pub password_hash: String,
+    /// Set for accounts created with a temporary password until the user picks their own.
+    pub password_change_required: bool,
 
+    /// Base32 TOTP secret; pending until `totp_enabled_at` is set.
+    #[graphql(visible = false)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<NaiveDateTime>,
+    /// Time step of the last TOTP code accepted, so no code is accepted twice.
     #[graphql(visible = false)]
-    pub refresh_token: Option<String>,
+    pub totp_last_step: Option<i64>,
 
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
@@ -53,6 +87,22 @@ pub enum Relation {
     Room,
     #[sea_orm(has_many = "super::user_achievment::Entity")]
     UserAchievment,
+    #[sea_orm(has_many = "super::session::Entity")]
+    Session,
+    #[sea_orm(has_many = "super::user_token::Entity")]
+    UserToken,
+    #[sea_orm(has_many = "super::recovery_code::Entity")]
+    RecoveryCode,
+    #[sea_orm(has_many = "super::teacher_application::Entity")]
+    TeacherApplication,
+    #[sea_orm(has_many = "super::personal_access_token::Entity")]
+    PersonalAccessToken,
+    #[sea_orm(has_many = "super::room_join_request::Entity")]
+    RoomJoinRequest,
+    #[sea_orm(has_many = "super::announcement_read::Entity")]
+    AnnouncementRead,
+    #[sea_orm(has_many = "super::submission::Entity")]
+    Submission,
 }
 
 impl Related<super::user_room::Entity> for Entity {
@@ -73,6 +123,54 @@ impl Related<super::user_achievment::Entity> for Entity {
     }
 }
 
+impl Related<super::session::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Session.def()
+    }
+}
+
+impl Related<super::user_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserToken.def()
+    }
+}
+
+impl Related<super::recovery_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RecoveryCode.def()
+    }
+}
+
+impl Related<super::teacher_application::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TeacherApplication.def()
+    }
+}
+
+impl Related<super::personal_access_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PersonalAccessToken.def()
+    }
+}
+
+impl Related<super::room_join_request::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RoomJoinRequest.def()
+    }
+}
+
+impl Related<super::announcement_read::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AnnouncementRead.def()
+    }
+}
+
+impl Related<super::submission::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Submission.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
 
 impl Entity {
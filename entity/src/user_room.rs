@@ -1,6 +1,21 @@
-use async_graphql::SimpleObject;
+use async_graphql::{Enum, SimpleObject};
 use sea_orm::entity::prelude::*;
 
+/// What a member may do in a room, from most to least privileged.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Enum)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum MemberRole {
+    #[sea_orm(string_value = "owner")]
+    Owner,
+    /// Can create tasks and manage students, but not the room itself.
+    #[sea_orm(string_value = "co_teacher")]
+    CoTeacher,
+    #[sea_orm(string_value = "assistant")]
+    Assistant,
+    #[sea_orm(string_value = "student")]
+    Student,
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, SimpleObject)]
 #[sea_orm(table_name = "user_room")]
 #[graphql(name = "UserRoomModel")]
@@ -10,6 +25,7 @@ pub struct Model {
     pub user_id: i32,
     pub room_id: i32,
     pub string: String,
+    pub role: MemberRole,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,12 +1,50 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20231101_000002_create_session_table;
+mod m20231101_000003_create_user_token_table;
+mod m20231101_000004_add_user_email_verified_at;
+mod m20231101_000005_add_user_token_new_email;
+mod m20231101_000006_create_login_attempt_table;
+mod m20231101_000007_add_totp;
+mod m20231101_000008_create_teacher_application_table;
+mod m20231101_000009_create_personal_access_token_table;
+mod m20231101_000010_create_room_invite_table;
+mod m20231101_000011_add_user_room_role;
+mod m20231101_000012_add_room_details;
+mod m20231101_000013_create_room_join_request_table;
+mod m20231101_000014_create_room_ownership_transfer_table;
+mod m20231101_000015_add_user_password_change_required;
+mod m20231101_000016_create_announcement_tables;
+mod m20231101_000017_add_task_schedule;
+mod m20231101_000018_create_submission_table;
+mod m20231101_000019_add_user_totp_last_step;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20231101_000002_create_session_table::Migration),
+            Box::new(m20231101_000003_create_user_token_table::Migration),
+            Box::new(m20231101_000004_add_user_email_verified_at::Migration),
+            Box::new(m20231101_000005_add_user_token_new_email::Migration),
+            Box::new(m20231101_000006_create_login_attempt_table::Migration),
+            Box::new(m20231101_000007_add_totp::Migration),
+            Box::new(m20231101_000008_create_teacher_application_table::Migration),
+            Box::new(m20231101_000009_create_personal_access_token_table::Migration),
+            Box::new(m20231101_000010_create_room_invite_table::Migration),
+            Box::new(m20231101_000011_add_user_room_role::Migration),
+            Box::new(m20231101_000012_add_room_details::Migration),
+            Box::new(m20231101_000013_create_room_join_request_table::Migration),
+            Box::new(m20231101_000014_create_room_ownership_transfer_table::Migration),
+            Box::new(m20231101_000015_add_user_password_change_required::Migration),
+            Box::new(m20231101_000016_create_announcement_tables::Migration),
+            Box::new(m20231101_000017_add_task_schedule::Migration),
+            Box::new(m20231101_000018_create_submission_table::Migration),
+            Box::new(m20231101_000019_add_user_totp_last_step::Migration),
+        ]
     }
 }
@@ -0,0 +1,110 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TeacherApplication::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TeacherApplication::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TeacherApplication::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TeacherApplication::School)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TeacherApplication::Subject)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TeacherApplication::ProofNote)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TeacherApplication::Status)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TeacherApplication::ReviewedBy).integer())
+                    .col(ColumnDef::new(TeacherApplication::ReviewNote).text())
+                    .col(
+                        ColumnDef::new(TeacherApplication::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TeacherApplication::ReviewedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-teacher_application-user_id")
+                            .from(TeacherApplication::Table, TeacherApplication::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-teacher_application-reviewed_by")
+                            .from(TeacherApplication::Table, TeacherApplication::ReviewedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-teacher_application-status")
+                    .table(TeacherApplication::Table)
+                    .col(TeacherApplication::Status)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TeacherApplication::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum TeacherApplication {
+    Table,
+    Id,
+    UserId,
+    School,
+    Subject,
+    ProofNote,
+    Status,
+    ReviewedBy,
+    ReviewNote,
+    CreatedAt,
+    ReviewedAt,
+}
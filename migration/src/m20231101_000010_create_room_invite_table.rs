@@ -0,0 +1,101 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RoomInvite::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RoomInvite::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(RoomInvite::RoomId).integer().not_null())
+                    .col(
+                        ColumnDef::new(RoomInvite::Code)
+                            .string()
+                            .unique_key()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RoomInvite::CreatedBy).integer().not_null())
+                    .col(ColumnDef::new(RoomInvite::MaxUses).integer())
+                    .col(
+                        ColumnDef::new(RoomInvite::Uses)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(RoomInvite::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(RoomInvite::ExpiresAt).date_time())
+                    .col(ColumnDef::new(RoomInvite::RevokedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-room_invite-room_id")
+                            .from(RoomInvite::Table, RoomInvite::RoomId)
+                            .to(Room::Table, Room::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-room_invite-created_by")
+                            .from(RoomInvite::Table, RoomInvite::CreatedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Owners used to add themselves with `addToRoom`, which is gone; make sure every
+        // owner is a member of their room.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"INSERT INTO "user_room" ("user_id", "room_id", "string")
+                SELECT "owner", "id", "owner" || '-' || "id" FROM "room"
+                ON CONFLICT ("string") DO NOTHING"#,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RoomInvite::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Room {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum RoomInvite {
+    Table,
+    Id,
+    RoomId,
+    Code,
+    CreatedBy,
+    MaxUses,
+    Uses,
+    CreatedAt,
+    ExpiresAt,
+    RevokedAt,
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserRoom::Table)
+                    .add_column(
+                        ColumnDef::new(UserRoom::Role)
+                            .string()
+                            .not_null()
+                            .default("student"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // Owners get a membership row, which the role checks rely on, even for rooms
+        // that never had one.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"INSERT INTO "user_room" ("user_id", "room_id", "string", "role")
+                SELECT "owner", "id", "owner" || '-' || "id", 'owner' FROM "room"
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM "user_room"
+                    WHERE "user_room"."room_id" = "room"."id"
+                        AND "user_room"."user_id" = "room"."owner"
+                )"#,
+            )
+            .await?;
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"UPDATE "user_room" SET "role" = 'owner' FROM "room"
+                WHERE "room"."id" = "user_room"."room_id" AND "room"."owner" = "user_room"."user_id""#,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserRoom::Table)
+                    .drop_column(UserRoom::Role)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserRoom {
+    Table,
+    Role,
+}
@@ -0,0 +1,103 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Room::Table)
+                    .add_column(ColumnDef::new(Room::Description).text())
+                    .add_column(ColumnDef::new(Room::Subject).string())
+                    .add_column(ColumnDef::new(Room::ArchivedAt).date_time())
+                    .to_owned(),
+            )
+            .await?;
+        // Deleting a room takes its tasks and memberships with it.
+        room_foreign_keys(manager, ForeignKeyAction::Cascade).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        room_foreign_keys(manager, ForeignKeyAction::NoAction).await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Room::Table)
+                    .drop_column(Room::Description)
+                    .drop_column(Room::Subject)
+                    .drop_column(Room::ArchivedAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Recreates the foreign keys from `task` and `user_room` to `room` with `on_delete`.
+async fn room_foreign_keys(
+    manager: &SchemaManager<'_>,
+    on_delete: ForeignKeyAction,
+) -> Result<(), DbErr> {
+    manager
+        .drop_foreign_key(
+            ForeignKey::drop()
+                .name("fk-task-room_id")
+                .table(Task::Table)
+                .to_owned(),
+        )
+        .await?;
+    manager
+        .create_foreign_key(
+            ForeignKey::create()
+                .name("fk-task-room_id")
+                .from(Task::Table, Task::RoomId)
+                .to(Room::Table, Room::Id)
+                .on_delete(on_delete)
+                .to_owned(),
+        )
+        .await?;
+    manager
+        .drop_foreign_key(
+            ForeignKey::drop()
+                .name("fk-user_room-room_id")
+                .table(UserRoom::Table)
+                .to_owned(),
+        )
+        .await?;
+    manager
+        .create_foreign_key(
+            ForeignKey::create()
+                .name("fk-user_room-room_id")
+                .from(UserRoom::Table, UserRoom::RoomId)
+                .to(Room::Table, Room::Id)
+                .on_delete(on_delete)
+                .to_owned(),
+        )
+        .await?;
+    Ok(())
+}
+
+#[derive(DeriveIden)]
+enum Room {
+    Table,
+    Id,
+    Description,
+    Subject,
+    ArchivedAt,
+}
+
+#[derive(DeriveIden)]
+enum Task {
+    Table,
+    RoomId,
+}
+
+#[derive(DeriveIden)]
+enum UserRoom {
+    Table,
+    RoomId,
+}
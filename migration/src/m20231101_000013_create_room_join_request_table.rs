@@ -0,0 +1,112 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RoomJoinRequest::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RoomJoinRequest::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RoomJoinRequest::RoomId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RoomJoinRequest::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RoomJoinRequest::Message).text())
+                    .col(
+                        ColumnDef::new(RoomJoinRequest::Status)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RoomJoinRequest::ReviewedBy).integer())
+                    .col(
+                        ColumnDef::new(RoomJoinRequest::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RoomJoinRequest::ReviewedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-room_join_request-room_id")
+                            .from(RoomJoinRequest::Table, RoomJoinRequest::RoomId)
+                            .to(Room::Table, Room::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-room_join_request-user_id")
+                            .from(RoomJoinRequest::Table, RoomJoinRequest::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-room_join_request-reviewed_by")
+                            .from(RoomJoinRequest::Table, RoomJoinRequest::ReviewedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-room_join_request-room_id-status")
+                    .table(RoomJoinRequest::Table)
+                    .col(RoomJoinRequest::RoomId)
+                    .col(RoomJoinRequest::Status)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RoomJoinRequest::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Room {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum RoomJoinRequest {
+    Table,
+    Id,
+    RoomId,
+    UserId,
+    Message,
+    Status,
+    ReviewedBy,
+    CreatedAt,
+    ReviewedAt,
+}
@@ -0,0 +1,114 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RoomOwnershipTransfer::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RoomOwnershipTransfer::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(RoomOwnershipTransfer::RoomId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RoomOwnershipTransfer::FromUserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RoomOwnershipTransfer::ToUserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RoomOwnershipTransfer::Status)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RoomOwnershipTransfer::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RoomOwnershipTransfer::RespondedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-room_ownership_transfer-room_id")
+                            .from(RoomOwnershipTransfer::Table, RoomOwnershipTransfer::RoomId)
+                            .to(Room::Table, Room::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-room_ownership_transfer-from_user_id")
+                            .from(RoomOwnershipTransfer::Table, RoomOwnershipTransfer::FromUserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-room_ownership_transfer-to_user_id")
+                            .from(RoomOwnershipTransfer::Table, RoomOwnershipTransfer::ToUserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-room_ownership_transfer-to_user_id-status")
+                    .table(RoomOwnershipTransfer::Table)
+                    .col(RoomOwnershipTransfer::ToUserId)
+                    .col(RoomOwnershipTransfer::Status)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RoomOwnershipTransfer::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Room {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum RoomOwnershipTransfer {
+    Table,
+    Id,
+    RoomId,
+    FromUserId,
+    ToUserId,
+    Status,
+    CreatedAt,
+    RespondedAt,
+}
@@ -0,0 +1,170 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Announcement::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Announcement::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Announcement::RoomId).integer().not_null())
+                    .col(ColumnDef::new(Announcement::AuthorId).integer())
+                    .col(ColumnDef::new(Announcement::Title).string().not_null())
+                    .col(ColumnDef::new(Announcement::Body).text().not_null())
+                    .col(ColumnDef::new(Announcement::PinnedAt).date_time())
+                    .col(
+                        ColumnDef::new(Announcement::PublishAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Announcement::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Announcement::UpdatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-announcement-room_id")
+                            .from(Announcement::Table, Announcement::RoomId)
+                            .to(Room::Table, Room::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-announcement-author_id")
+                            .from(Announcement::Table, Announcement::AuthorId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-announcement-room_id-publish_at")
+                    .table(Announcement::Table)
+                    .col(Announcement::RoomId)
+                    .col(Announcement::PublishAt)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(AnnouncementRead::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AnnouncementRead::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(AnnouncementRead::AnnouncementId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AnnouncementRead::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AnnouncementRead::ReadAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-announcement_read-announcement_id")
+                            .from(AnnouncementRead::Table, AnnouncementRead::AnnouncementId)
+                            .to(Announcement::Table, Announcement::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-announcement_read-user_id")
+                            .from(AnnouncementRead::Table, AnnouncementRead::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-announcement_read-announcement_id-user_id")
+                    .table(AnnouncementRead::Table)
+                    .col(AnnouncementRead::AnnouncementId)
+                    .col(AnnouncementRead::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AnnouncementRead::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Announcement::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Room {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Announcement {
+    Table,
+    Id,
+    RoomId,
+    AuthorId,
+    Title,
+    Body,
+    PinnedAt,
+    PublishAt,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum AnnouncementRead {
+    Table,
+    Id,
+    AnnouncementId,
+    UserId,
+    ReadAt,
+}
@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Existing tasks were visible to everyone, so they start out published.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Task::Table)
+                    .add_column(
+                        ColumnDef::new(Task::Status)
+                            .string()
+                            .not_null()
+                            .default("published"),
+                    )
+                    .add_column(ColumnDef::new(Task::PublishAt).date_time())
+                    .add_column(ColumnDef::new(Task::DueAt).date_time())
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Task::Table)
+                    .drop_column(Task::Status)
+                    .drop_column(Task::PublishAt)
+                    .drop_column(Task::DueAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Task {
+    Table,
+    Status,
+    PublishAt,
+    DueAt,
+}
@@ -0,0 +1,110 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Submission::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Submission::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Submission::TaskId).integer().not_null())
+                    .col(ColumnDef::new(Submission::UserId).integer().not_null())
+                    .col(ColumnDef::new(Submission::Body).text().not_null())
+                    .col(ColumnDef::new(Submission::Attachments).text().not_null())
+                    .col(ColumnDef::new(Submission::Status).string().not_null())
+                    .col(ColumnDef::new(Submission::Attempt).integer().not_null())
+                    .col(ColumnDef::new(Submission::Grade).integer())
+                    .col(ColumnDef::new(Submission::Feedback).text())
+                    .col(ColumnDef::new(Submission::GradedBy).integer())
+                    .col(
+                        ColumnDef::new(Submission::SubmittedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Submission::GradedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-submission-task_id")
+                            .from(Submission::Table, Submission::TaskId)
+                            .to(Task::Table, Task::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-submission-user_id")
+                            .from(Submission::Table, Submission::UserId)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-submission-graded_by")
+                            .from(Submission::Table, Submission::GradedBy)
+                            .to(User::Table, User::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // One submission per student and task; resubmitting updates it.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-submission-task_id-user_id")
+                    .table(Submission::Table)
+                    .col(Submission::TaskId)
+                    .col(Submission::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Submission::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Task {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Submission {
+    Table,
+    Id,
+    TaskId,
+    UserId,
+    Body,
+    Attachments,
+    Status,
+    Attempt,
+    Grade,
+    Feedback,
+    GradedBy,
+    SubmittedAt,
+    GradedAt,
+}
@@ -0,0 +1,453 @@
+use async_graphql::Object;
+use chrono::{Duration, Utc};
+use email_address::EmailAddress;
+use entity::{
+    user::{self, Entity as User},
+    user_token::{self, Entity as UserToken, TokenKind},
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, Set,
+};
+
+use crate::{
+    auth::{
+        current_user, hash_password, hash_token, random_token, session_user, verify_password,
+        AuthUser, ClientInfo,
+    },
+    mailer::Email,
+    session, Context, LoginResponse,
+};
+
+/// Lifetime of a password reset token, in minutes.
+const RESET_EXPIRATION: i64 = 30;
+/// Lifetime of an email verification token, in minutes.
+const VERIFICATION_EXPIRATION: i64 = 24 * 60;
+
+/// Creates a `kind` token for `user_id`, superseding any earlier unused one.
+pub async fn issue_token(
+    db: &DatabaseConnection,
+    secret: &str,
+    user_id: i32,
+    kind: TokenKind,
+    new_email: Option<String>,
+    minutes: i64,
+) -> Result<String, DbErr> {
+    let now = Utc::now().naive_utc();
+
+    UserToken::update_many()
+        .col_expr(user_token::Column::UsedAt, Expr::value(now))
+        .filter(user_token::Column::UserId.eq(user_id))
+        .filter(user_token::Column::Kind.eq(kind))
+        .filter(user_token::Column::UsedAt.is_null())
+        .exec(db)
+        .await?;
+
+    let token = random_token();
+
+    user_token::ActiveModel {
+        user_id: Set(user_id),
+        kind: Set(kind),
+        token_hash: Set(hash_token(secret, &token)),
+        new_email: Set(new_email),
+        created_at: Set(now),
+        expires_at: Set(now + Duration::minutes(minutes)),
+        used_at: Set(None),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(token)
+}
+
+/// Looks up a live `kind` token without using it up.
+pub async fn find_token(
+    db: &DatabaseConnection,
+    secret: &str,
+    token: &str,
+    kind: TokenKind,
+) -> Result<user_token::Model, async_graphql::Error> {
+    let found: Option<user_token::Model> = UserToken::find()
+        .filter(user_token::Column::TokenHash.eq(hash_token(secret, token)))
+        .filter(user_token::Column::Kind.eq(kind))
+        .filter(user_token::Column::UsedAt.is_null())
+        .filter(user_token::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+        .one(db)
+        .await?;
+
+    match found {
+        Some(found) => Ok(found),
+        None => Err(async_graphql::Error::new("Wrong token".to_string())),
+    }
+}
+
+/// Marks `token` as used, failing if someone else used it first.
+pub async fn use_token(
+    db: &DatabaseConnection,
+    token: &user_token::Model,
+) -> Result<(), async_graphql::Error> {
+    let used = UserToken::update_many()
+        .col_expr(
+            user_token::Column::UsedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(user_token::Column::Id.eq(token.id))
+        .filter(user_token::Column::UsedAt.is_null())
+        .exec(db)
+        .await?;
+
+    if used.rows_affected == 0 {
+        return Err(async_graphql::Error::new("Wrong token".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Marks a live `kind` token as used and returns it.
+async fn consume_token(
+    db: &DatabaseConnection,
+    secret: &str,
+    token: &str,
+    kind: TokenKind,
+) -> Result<user_token::Model, async_graphql::Error> {
+    let found = find_token(db, secret, token, kind).await?;
+    use_token(db, &found).await?;
+    Ok(found)
+}
+
+/// Loads the current user and checks that `password` is theirs.
+pub async fn reauthenticate(
+    ctx: &async_graphql::Context<'_>,
+    password: &str,
+) -> Result<user::Model, async_graphql::Error> {
+    let auth_user = current_user(ctx)?;
+    check_password(ctx.data::<Context>().unwrap(), auth_user, password).await
+}
+
+async fn check_password(
+    my_ctx: &Context,
+    auth_user: &AuthUser,
+    password: &str,
+) -> Result<user::Model, async_graphql::Error> {
+    let user: Option<user::Model> = User::find_by_id(auth_user.id).one(&my_ctx.db).await?;
+
+    match user {
+        Some(user) if verify_password(password, &user.password_hash) => Ok(user),
+        _ => Err(async_graphql::Error::new("Wrong password".to_string())),
+    }
+}
+
+/// Mails `user` a token for `verifyEmail`.
+pub async fn send_verification(
+    my_ctx: &Context,
+    user: &user::Model,
+) -> Result<(), async_graphql::Error> {
+    let token = issue_token(
+        &my_ctx.db,
+        &my_ctx.refr_key,
+        user.id,
+        TokenKind::EmailVerification,
+        None,
+        VERIFICATION_EXPIRATION,
+    )
+    .await?;
+
+    my_ctx
+        .mailer
+        .send(Email {
+            to: user.email.clone(),
+            subject: "Verify your email".to_string(),
+            body: format!(
+                "Use this code to verify your email address: {}\nIt expires in {} hours.",
+                token,
+                VERIFICATION_EXPIRATION / 60
+            ),
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct AccountMutation;
+
+#[Object]
+impl AccountMutation {
+    /// Mails a password reset token to `email`. Succeeds whether or not the address is
+    /// registered, so it cannot be used to probe for accounts.
+    async fn request_password_reset(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        email: String,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let user: Option<user::Model> = User::find_by_email(email).one(&my_ctx.db).await?;
+
+        let user = match user {
+            Some(user) => user,
+            None => return Ok(true),
+        };
+
+        let token = issue_token(
+            &my_ctx.db,
+            &my_ctx.refr_key,
+            user.id,
+            TokenKind::PasswordReset,
+            None,
+            RESET_EXPIRATION,
+        )
+        .await?;
+
+        // A failed send must look the same as an unknown address.
+        if let Err(err) = my_ctx
+            .mailer
+            .send(Email {
+                to: user.email,
+                subject: "Password reset".to_string(),
+                body: format!(
+                    "Use this code to reset your password: {}\nIt expires in {} minutes.",
+                    token, RESET_EXPIRATION
+                ),
+            })
+            .await
+        {
+            tracing::error!("could not send password reset email: {}", err);
+        }
+
+        Ok(true)
+    }
+
+    /// Sets a new password using a token from `requestPasswordReset` and ends all sessions.
+    async fn reset_password(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        token: String,
+        new_password: String,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let token = find_token(
+            &my_ctx.db,
+            &my_ctx.refr_key,
+            &token,
+            TokenKind::PasswordReset,
+        )
+        .await?;
+
+        let user: Option<user::Model> = User::find_by_id(token.user_id).one(&my_ctx.db).await?;
+
+        let user = match user {
+            Some(user) => user,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        // Check before using the token up, so a rejected password can be retried.
+        my_ctx
+            .config
+            .password_policy
+            .check(&new_password, &user.username, &user.email)?;
+        use_token(&my_ctx.db, &token).await?;
+
+        user::ActiveModel {
+            id: Set(token.user_id),
+            password_hash: Set(hash_password(&new_password, &my_ctx.config.argon2)?),
+            password_change_required: Set(false),
+            updated_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .update(&my_ctx.db)
+        .await?;
+
+        session::revoke_all(&my_ctx.db, token.user_id).await?;
+
+        Ok(true)
+    }
+
+    /// Mails a new verification token to `email` if it belongs to an unverified account.
+    async fn request_email_verification(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        email: String,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let user: Option<user::Model> = User::find_by_email(email).one(&my_ctx.db).await?;
+
+        if let Some(user) = user {
+            // A failed send must look the same as an unknown or verified address.
+            if user.email_verified_at.is_none() {
+                if let Err(err) = send_verification(my_ctx, &user).await {
+                    tracing::error!("could not send verification email: {}", err.message);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Confirms the email address a verification token was sent to.
+    async fn verify_email(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        token: String,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let token = consume_token(
+            &my_ctx.db,
+            &my_ctx.refr_key,
+            &token,
+            TokenKind::EmailVerification,
+        )
+        .await?;
+
+        let now = Utc::now().naive_utc();
+
+        user::ActiveModel {
+            id: Set(token.user_id),
+            email_verified_at: Set(Some(now)),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .update(&my_ctx.db)
+        .await?;
+
+        Ok(true)
+    }
+
+    /// Replaces the current user's password. Every other session is ended and the
+    /// caller gets a fresh pair of tokens. This is the only thing a user with a
+    /// temporary password can do.
+    async fn change_password(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        current_password: String,
+        new_password: String,
+    ) -> Result<LoginResponse, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = session_user(ctx)?;
+        let user = check_password(my_ctx, auth_user, &current_password).await?;
+
+        my_ctx
+            .config
+            .password_policy
+            .check(&new_password, &user.username, &user.email)?;
+
+        let mut newuser: user::ActiveModel = user.into();
+        newuser.password_hash = Set(hash_password(&new_password, &my_ctx.config.argon2)?);
+        newuser.password_change_required = Set(false);
+        newuser.updated_at = Set(Utc::now().naive_utc());
+        let user: user::Model = newuser.update(&my_ctx.db).await?;
+
+        session::revoke_all(&my_ctx.db, user.id).await?;
+
+        let client = ctx.data::<ClientInfo>()?;
+        let refresh_token = session::start(&my_ctx.db, &my_ctx.refr_key, user.id, client).await?;
+        my_ctx.issue_tokens(&user, refresh_token)
+    }
+
+    /// Starts moving the current user to `new_email`. The address only changes once
+    /// the token mailed to it is passed to `confirmEmailChange`.
+    async fn change_email(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        password: String,
+        new_email: String,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let user = reauthenticate(ctx, &password).await?;
+
+        if !EmailAddress::is_valid(&new_email) {
+            return Err(async_graphql::Error::new("Wrong email".to_string()));
+        }
+
+        let taken: Option<user::Model> = User::find_by_email(new_email.clone())
+            .one(&my_ctx.db)
+            .await?;
+        if taken.is_some() {
+            return Err(async_graphql::Error::new(
+                "this email is already taken".to_string(),
+            ));
+        }
+
+        let token = issue_token(
+            &my_ctx.db,
+            &my_ctx.refr_key,
+            user.id,
+            TokenKind::EmailChange,
+            Some(new_email.clone()),
+            VERIFICATION_EXPIRATION,
+        )
+        .await?;
+
+        my_ctx
+            .mailer
+            .send(Email {
+                to: new_email.clone(),
+                subject: "Confirm your new email".to_string(),
+                body: format!(
+                    "Use this code to confirm your new email address: {}\nIt expires in {} hours.",
+                    token,
+                    VERIFICATION_EXPIRATION / 60
+                ),
+            })
+            .await?;
+
+        my_ctx
+            .mailer
+            .send(Email {
+                to: user.email,
+                subject: "Your email is being changed".to_string(),
+                body: format!(
+                    "Someone asked to move your account to {}. If it was not you, reset your password.",
+                    new_email
+                ),
+            })
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Switches the account to the address a `changeEmail` token was sent to.
+    async fn confirm_email_change(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        token: String,
+    ) -> Result<user::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let token =
+            consume_token(&my_ctx.db, &my_ctx.refr_key, &token, TokenKind::EmailChange).await?;
+
+        let new_email = match token.new_email {
+            Some(new_email) => new_email,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        // Someone may have registered the address since the change was requested.
+        let taken: Option<user::Model> = User::find_by_email(new_email.clone())
+            .one(&my_ctx.db)
+            .await?;
+        if taken.is_some() {
+            return Err(async_graphql::Error::new(
+                "this email is already taken".to_string(),
+            ));
+        }
+
+        let now = Utc::now().naive_utc();
+
+        let user: user::Model = user::ActiveModel {
+            id: Set(token.user_id),
+            email: Set(new_email),
+            email_verified_at: Set(Some(now)),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .update(&my_ctx.db)
+        .await?;
+
+        Ok(user)
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+use async_graphql::Object;
+use chrono::{NaiveDateTime, Utc};
+use entity::{
+    announcement::{self, Entity as Announcement},
+    announcement_read::{self, Entity as AnnouncementRead},
+    room,
+};
+use sea_orm::{
+    sea_query::{Expr, OnConflict, Query},
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, ModelTrait, Order,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+
+use crate::{
+    auth::{current_user, scoped_user, AuthUser},
+    events::Event,
+    membership::{is_staff, require_role, MEMBERS, STAFF},
+    personal_token::Scope,
+    rooms::writable,
+    Context,
+};
+
+/// Published announcements the user has not read yet, per room in `room_ids`.
+pub async fn unread_counts(
+    db: &DatabaseConnection,
+    user_id: i32,
+    room_ids: &[i32],
+) -> Result<HashMap<i32, i64>, DbErr> {
+    let counts: Vec<(i32, i64)> = Announcement::find()
+        .select_only()
+        .column(announcement::Column::RoomId)
+        .column_as(announcement::Column::Id.count(), "count")
+        .filter(announcement::Column::RoomId.is_in(room_ids.to_vec()))
+        .filter(announcement::Column::PublishAt.lte(Utc::now().naive_utc()))
+        .filter(
+            announcement::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(announcement_read::Column::AnnouncementId)
+                    .from(AnnouncementRead)
+                    .and_where(announcement_read::Column::UserId.eq(user_id))
+                    .to_owned(),
+            ),
+        )
+        .group_by(announcement::Column::RoomId)
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(counts.into_iter().collect())
+}
+
+/// Loads announcement `id` for the staff of its room.
+async fn staff_announcement(
+    my_ctx: &Context,
+    user: &AuthUser,
+    id: i32,
+) -> Result<(announcement::Model, room::Model), async_graphql::Error> {
+    let announcement: Option<announcement::Model> =
+        Announcement::find_by_id(id).one(&my_ctx.db).await?;
+
+    let announcement = match announcement {
+        Some(announcement) => announcement,
+        None => {
+            return Err(async_graphql::Error::new(
+                "announcement not found".to_string(),
+            ))
+        }
+    };
+
+    let room = require_role(my_ctx, user, announcement.room_id, STAFF).await?;
+    Ok((announcement, room))
+}
+
+fn required(field: &str, value: String) -> Result<String, async_graphql::Error> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(async_graphql::Error::new(format!(
+            "the {} must not be empty",
+            field
+        )));
+    }
+    Ok(value.to_string())
+}
+
+#[derive(Default)]
+pub struct AnnouncementQuery;
+
+#[Object]
+impl AnnouncementQuery {
+    /// The announcements of `room_id`, pinned ones first and then newest first. Staff
+    /// also see scheduled ones.
+    async fn announcements(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<Vec<announcement::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+        require_role(my_ctx, auth_user, room_id, MEMBERS).await?;
+
+        let mut select = Announcement::find().filter(announcement::Column::RoomId.eq(room_id));
+        if !is_staff(my_ctx, auth_user, room_id).await? {
+            select = select.filter(announcement::Column::PublishAt.lte(Utc::now().naive_utc()));
+        }
+
+        let mut announcements: Vec<announcement::Model> = select
+            .order_by(
+                Expr::col(announcement::Column::PinnedAt).is_null(),
+                Order::Asc,
+            )
+            .order_by_desc(announcement::Column::PinnedAt)
+            .order_by_desc(announcement::Column::PublishAt)
+            .order_by_desc(announcement::Column::Id)
+            .all(&my_ctx.db)
+            .await?;
+
+        let ids: Vec<i32> = announcements
+            .iter()
+            .map(|announcement| announcement.id)
+            .collect();
+        let reads: HashMap<i32, NaiveDateTime> = AnnouncementRead::find()
+            .filter(announcement_read::Column::UserId.eq(auth_user.id))
+            .filter(announcement_read::Column::AnnouncementId.is_in(ids))
+            .all(&my_ctx.db)
+            .await?
+            .into_iter()
+            .map(|read| (read.announcement_id, read.read_at))
+            .collect();
+
+        for announcement in &mut announcements {
+            announcement.read_at = reads.get(&announcement.id).copied();
+        }
+
+        Ok(announcements)
+    }
+
+    /// Who has read announcement `id`, for the staff of its room.
+    async fn announcement_reads(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+    ) -> Result<Vec<announcement_read::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+        let (announcement, _) = staff_announcement(my_ctx, auth_user, id).await?;
+
+        let reads: Vec<announcement_read::Model> = announcement
+            .find_related(AnnouncementRead)
+            .order_by_asc(announcement_read::Column::ReadAt)
+            .all(&my_ctx.db)
+            .await?;
+        Ok(reads)
+    }
+}
+
+#[derive(Default)]
+pub struct AnnouncementMutation;
+
+#[Object]
+impl AnnouncementMutation {
+    /// Posts an announcement to `room_id`. It stays hidden from students until
+    /// `publish_at`, if that is given.
+    async fn post_announcement(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        title: String,
+        body: String,
+        #[graphql(default)] pinned: bool,
+        publish_at: Option<NaiveDateTime>,
+    ) -> Result<announcement::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let room = require_role(my_ctx, auth_user, room_id, STAFF).await?;
+        writable(&room)?;
+
+        let now = Utc::now().naive_utc();
+        let announcement = announcement::ActiveModel {
+            room_id: Set(room_id),
+            author_id: Set(Some(auth_user.id)),
+            title: Set(required("title", title)?),
+            body: Set(required("body", body)?),
+            pinned_at: Set(if pinned { Some(now) } else { None }),
+            publish_at: Set(publish_at.unwrap_or(now)),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&my_ctx.db)
+        .await?;
+
+        // Scheduled announcements show up in the feed later, without an event.
+        if announcement.publish_at <= now {
+            my_ctx.events.publish(Event::AnnouncementPosted {
+                room_id,
+                announcement_id: announcement.id,
+            });
+        }
+
+        Ok(announcement)
+    }
+
+    /// Edits announcement `id`; fields that are not given stay as they are.
+    async fn update_announcement(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+        title: Option<String>,
+        body: Option<String>,
+        publish_at: Option<NaiveDateTime>,
+    ) -> Result<announcement::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let (announcement, room) = staff_announcement(my_ctx, auth_user, id).await?;
+        writable(&room)?;
+
+        let mut announcement: announcement::ActiveModel = announcement.into();
+
+        if let Some(title) = title {
+            announcement.title = Set(required("title", title)?);
+        }
+
+        if let Some(body) = body {
+            announcement.body = Set(required("body", body)?);
+        }
+
+        if let Some(publish_at) = publish_at {
+            announcement.publish_at = Set(publish_at);
+        }
+
+        announcement.updated_at = Set(Utc::now().naive_utc());
+        let announcement: announcement::Model = announcement.update(&my_ctx.db).await?;
+        Ok(announcement)
+    }
+
+    /// Pins or unpins announcement `id`.
+    async fn pin_announcement(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+        pinned: bool,
+    ) -> Result<announcement::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let (announcement, room) = staff_announcement(my_ctx, auth_user, id).await?;
+        writable(&room)?;
+
+        let now = Utc::now().naive_utc();
+        let mut announcement: announcement::ActiveModel = announcement.into();
+        announcement.pinned_at = Set(if pinned { Some(now) } else { None });
+        announcement.updated_at = Set(now);
+        let announcement: announcement::Model = announcement.update(&my_ctx.db).await?;
+        Ok(announcement)
+    }
+
+    /// Deletes announcement `id` along with its read receipts.
+    async fn delete_announcement(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let (announcement, room) = staff_announcement(my_ctx, auth_user, id).await?;
+        writable(&room)?;
+
+        announcement.delete(&my_ctx.db).await?;
+        Ok(true)
+    }
+
+    /// Records that the current user has read announcement `id`. Marking it twice is fine.
+    async fn mark_announcement_read(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let announcement: Option<announcement::Model> =
+            Announcement::find_by_id(id).one(&my_ctx.db).await?;
+
+        let announcement = match announcement {
+            Some(announcement) => announcement,
+            None => {
+                return Err(async_graphql::Error::new(
+                    "announcement not found".to_string(),
+                ))
+            }
+        };
+
+        require_role(my_ctx, auth_user, announcement.room_id, MEMBERS).await?;
+        if announcement.publish_at > Utc::now().naive_utc()
+            && !is_staff(my_ctx, auth_user, announcement.room_id).await?
+        {
+            return Err(async_graphql::Error::new(
+                "announcement not found".to_string(),
+            ));
+        }
+
+        AnnouncementRead::insert(announcement_read::ActiveModel {
+            announcement_id: Set(announcement.id),
+            user_id: Set(auth_user.id),
+            read_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([
+                announcement_read::Column::AnnouncementId,
+                announcement_read::Column::UserId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&my_ctx.db)
+        .await?;
+
+        Ok(true)
+    }
+}
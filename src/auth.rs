@@ -0,0 +1,404 @@
+use actix_web::{http::header, HttpRequest};
+use argon2::{
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Algorithm, Argon2, Params, Version,
+};
+use async_graphql::Guard;
+use chrono::NaiveDateTime;
+use entity::user::{self, Role};
+use hmac::{Hmac, Mac};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::VerificationPolicy,
+    keys::KeySet,
+    personal_token::{self, Scope},
+    Context,
+};
+
+/// Claims carried by access tokens.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub id: i32,
+    pub email: String,
+    pub role: Role,
+    pub email_verified: bool,
+    /// Set while the user still has to replace a temporary password.
+    #[serde(default)]
+    pub password_change_required: bool,
+    /// Expiration as seconds since the unix epoch.
+    pub exp: usize,
+}
+
+impl Claims {
+    /// Claims for `user` that expire `minutes` from now.
+    pub fn new(user: &user::Model, minutes: usize) -> Self {
+        Self {
+            id: user.id,
+            email: user.email.clone(),
+            role: user.role,
+            email_verified: user.email_verified_at.is_some(),
+            password_change_required: user.password_change_required,
+            exp: now() + minutes * 60,
+        }
+    }
+
+    pub fn sign(&self, keys: &KeySet) -> Result<String, async_graphql::Error> {
+        keys.sign(self)
+            .map_err(|err| async_graphql::Error::new(err.to_string()))
+    }
+
+    /// Checks the signature and expiration of `token`.
+    pub fn verify(token: &str, keys: &KeySet) -> Result<Self, async_graphql::Error> {
+        keys.verify(token)
+            .map_err(|err| async_graphql::Error::new(err.to_string()))
+    }
+}
+
+/// Random hex string used for refresh tokens, family ids and mailed tokens.
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Tokens are stored as an HMAC keyed with `REFRESH_KEY`, never in plaintext.
+pub fn hash_token(secret: &str, token: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take a key of any size");
+    mac.update(token.as_bytes());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+pub fn hash_password(password: &str, params: &Params) -> Result<String, async_graphql::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| async_graphql::Error::new(err.to_string()))
+}
+
+/// Checks `password` against a stored Argon2 `password_hash`.
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Whether `password_hash` was made with something other than Argon2id and `params`.
+pub fn needs_rehash(password_hash: &str, params: &Params) -> bool {
+    let parsed = match PasswordHash::new(password_hash) {
+        Ok(parsed) => parsed,
+        Err(_) => return true,
+    };
+    if parsed.algorithm != Algorithm::Argon2id.ident()
+        || parsed.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+    match Params::try_from(&parsed) {
+        Ok(current) => {
+            current.m_cost() != params.m_cost()
+                || current.t_cost() != params.t_cost()
+                || current.p_cost() != params.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+fn now() -> usize {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as usize
+}
+
+/// The user an incoming request was made by, taken from a verified access token.
+#[derive(Clone, Debug)]
+pub struct AuthUser {
+    pub id: i32,
+    pub role: Role,
+    /// As of when the token was issued, so it only changes after a refresh.
+    pub email_verified: bool,
+    /// Blocks everything but `changePassword` until the temporary password is replaced.
+    pub password_change_required: bool,
+    /// What a personal access token was limited to; `None` for session tokens.
+    pub scopes: Option<Vec<Scope>>,
+    /// When the token stops being accepted; `None` for personal tokens that never expire.
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl AuthUser {
+    pub fn allows(&self, scope: Scope) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.contains(&scope),
+            None => true,
+        }
+    }
+}
+
+/// Checks access tokens and personal access tokens sent in the `Authorization` header.
+#[derive(Clone)]
+pub struct TokenVerifier {
+    keys: Arc<KeySet>,
+    db: DatabaseConnection,
+    refr_key: String,
+}
+
+impl TokenVerifier {
+    pub fn new(keys: Arc<KeySet>, db: DatabaseConnection, refr_key: String) -> Self {
+        Self { keys, db, refr_key }
+    }
+
+    pub fn verify(&self, access_token: &str) -> Result<AuthUser, async_graphql::Error> {
+        let claims = Claims::verify(access_token, &self.keys)?;
+        Ok(AuthUser {
+            id: claims.id,
+            role: claims.role,
+            email_verified: claims.email_verified,
+            password_change_required: claims.password_change_required,
+            scopes: None,
+            expires_at: NaiveDateTime::from_timestamp_opt(claims.exp as i64, 0),
+        })
+    }
+
+    /// Verifies the `Authorization: Bearer <token>` header of `req`, if there is one.
+    pub async fn authenticate(&self, req: &HttpRequest) -> Option<AuthUser> {
+        self.authenticate_token(bearer_token(req)?).await
+    }
+
+    /// Reads the token websocket clients send in their `connection_init` payload, either
+    /// as `{"Authorization": "Bearer <token>"}` or as `{"token": "<token>"}`.
+    pub async fn connection_init(
+        &self,
+        payload: serde_json::Value,
+    ) -> Result<async_graphql::Data, async_graphql::Error> {
+        let mut data = async_graphql::Data::default();
+
+        let token = payload
+            .get("Authorization")
+            .or_else(|| payload.get("authorization"))
+            .and_then(|value| value.as_str())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or_else(|| payload.get("token").and_then(|value| value.as_str()));
+
+        if let Some(token) = token {
+            match self.authenticate_token(token).await {
+                Some(user) => data.insert(user),
+                None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+            }
+        }
+
+        Ok(data)
+    }
+
+    async fn authenticate_token(&self, token: &str) -> Option<AuthUser> {
+        if token.starts_with(personal_token::TOKEN_PREFIX) {
+            return match personal_token::authenticate(&self.db, &self.refr_key, token).await {
+                Ok(user) => user,
+                Err(err) => {
+                    tracing::error!("could not check personal access token: {}", err);
+                    None
+                }
+            };
+        }
+
+        match self.verify(token) {
+            Ok(user) => Some(user),
+            Err(err) => {
+                tracing::debug!("rejected access token: {}", err.message);
+                None
+            }
+        }
+    }
+}
+
+/// Where a request came from, recorded on the sessions it starts.
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        Self {
+            user_agent: req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|agent| agent.to_str().ok())
+                .map(str::to_string),
+            ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+        }
+    }
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+fn password_changed(user: &AuthUser) -> Result<(), async_graphql::Error> {
+    if user.password_change_required {
+        return Err(async_graphql::Error::new(
+            "change your password first".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Like [`current_user`], but also admits users who still have to change their
+/// temporary password. Only `changePassword` should use it.
+pub fn session_user<'a>(
+    ctx: &'a async_graphql::Context<'_>,
+) -> Result<&'a AuthUser, async_graphql::Error> {
+    let user = ctx
+        .data_opt::<AuthUser>()
+        .ok_or_else(|| async_graphql::Error::new("you are not loged in".to_string()))?;
+
+    if user.scopes.is_some() {
+        return Err(async_graphql::Error::new(
+            "personal access tokens cannot do this".to_string(),
+        ));
+    }
+
+    Ok(user)
+}
+
+/// Returns the authenticated user of the current request. Personal access tokens are
+/// turned away; fields that accept them use [`scoped_user`] instead.
+pub fn current_user<'a>(
+    ctx: &'a async_graphql::Context<'_>,
+) -> Result<&'a AuthUser, async_graphql::Error> {
+    let user = session_user(ctx)?;
+    password_changed(user)?;
+    Ok(user)
+}
+
+/// Like [`current_user`], but also admits personal access tokens that carry `scope`.
+pub fn scoped_user<'a>(
+    ctx: &'a async_graphql::Context<'_>,
+    scope: Scope,
+) -> Result<&'a AuthUser, async_graphql::Error> {
+    let user = ctx
+        .data_opt::<AuthUser>()
+        .ok_or_else(|| async_graphql::Error::new("you are not loged in".to_string()))?;
+    password_changed(user)?;
+
+    if !user.allows(scope) {
+        return Err(async_graphql::Error::new(format!(
+            "this token is missing the {} scope",
+            scope.as_str()
+        )));
+    }
+
+    Ok(user)
+}
+
+/// Admits authenticated users whose role is at least `role`. Under
+/// [`VerificationPolicy::Teachers`] privileged roles also need a verified email.
+/// Personal access tokens are only admitted if the guard names a scope they carry.
+pub struct RoleGuard {
+    role: Role,
+    scope: Option<Scope>,
+}
+
+impl RoleGuard {
+    pub fn new(role: Role) -> Self {
+        Self { role, scope: None }
+    }
+
+    pub fn scope(mut self, scope: Scope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<()> {
+        let user = match self.scope {
+            Some(scope) => scoped_user(ctx, scope)?,
+            None => current_user(ctx)?,
+        };
+        if user.role < self.role {
+            return Err(async_graphql::Error::new(
+                "you do not have permission to do this".to_string(),
+            ));
+        }
+
+        let my_ctx = ctx.data::<Context>().unwrap();
+        if self.role >= Role::Teacher
+            && !user.email_verified
+            && my_ctx.config.email_verification == VerificationPolicy::Teachers
+        {
+            return Err(async_graphql::Error::new(
+                "verify your email first".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(m_cost: u32, t_cost: u32) -> Params {
+        Params::new(m_cost, t_cost, 1, None).unwrap()
+    }
+
+    #[test]
+    fn current_hash_is_kept() {
+        let hash = hash_password("hunter22", &params(1024, 1)).unwrap();
+        assert!(!needs_rehash(&hash, &params(1024, 1)));
+        assert!(verify_password("hunter22", &hash));
+    }
+
+    #[test]
+    fn changed_params_need_rehash() {
+        let hash = hash_password("hunter22", &params(1024, 1)).unwrap();
+        assert!(needs_rehash(&hash, &params(2048, 1)));
+        assert!(needs_rehash(&hash, &params(1024, 2)));
+    }
+
+    #[test]
+    fn other_algorithms_need_rehash() {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::new(Algorithm::Argon2i, Version::V0x13, params(1024, 1))
+            .hash_password(b"hunter22", &salt)
+            .unwrap()
+            .to_string();
+        assert!(needs_rehash(&hash, &params(1024, 1)));
+        assert!(needs_rehash("not a hash", &params(1024, 1)));
+    }
+
+    #[test]
+    fn older_claims_need_no_password_change() {
+        let claims: Claims = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "email": "student@example.com",
+            "role": "Student",
+            "email_verified": true,
+            "exp": 0,
+        }))
+        .unwrap();
+        assert!(!claims.password_change_required);
+    }
+}
@@ -0,0 +1,284 @@
+use chrono::Utc;
+use clap::{Parser, Subcommand, ValueEnum};
+use dotenvy::dotenv;
+use entity::{
+    achievment::{self, Entity as Achievment},
+    room::{self, Entity as Room},
+    user::{self, Entity as User, Role},
+    user_room::{self, Entity as UserRoom},
+};
+use hackaton::{auth, config::Config, session};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, Database, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use std::error::Error;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+/// Achievements created by `seed-achievements`, as (title, description).
+const STARTER_ACHIEVEMENTS: &[(&str, &str)] = &[
+    ("First steps", "Solved your first task."),
+    ("Team player", "Joined your first room."),
+    ("Streak", "Solved a task five days in a row."),
+    ("Perfectionist", "Got full marks on ten tasks."),
+    ("Explorer", "Solved tasks in three different rooms."),
+];
+
+/// Administration tasks that cannot be done through the GraphQL API.
+#[derive(Parser)]
+#[command(name = "hackaton-admin")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Creates an admin account. Prompts for the password.
+    CreateAdmin {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        last_name: String,
+        #[arg(long, default_value = "")]
+        school: String,
+    },
+    /// Changes the role of the user with this email or username.
+    SetRole { user: String, role: RoleArg },
+    /// Sets a new password, prompting for it, and ends the user's sessions.
+    ResetPassword { user: String },
+    /// Lists all rooms with their owner and member count.
+    ListRooms,
+    /// Applies or rolls back database migrations.
+    Migrate {
+        #[command(subcommand)]
+        direction: Migrate,
+    },
+    /// Creates the starter achievements that do not exist yet.
+    SeedAchievements,
+}
+
+#[derive(Subcommand)]
+enum Migrate {
+    /// Applies pending migrations, or only the next `steps`.
+    Up { steps: Option<u32> },
+    /// Rolls back the last `steps` migrations.
+    Down {
+        #[arg(default_value_t = 1)]
+        steps: u32,
+    },
+    /// Shows which migrations are applied.
+    Status,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum RoleArg {
+    Student,
+    Teacher,
+    Admin,
+}
+
+impl From<RoleArg> for Role {
+    fn from(role: RoleArg) -> Self {
+        match role {
+            RoleArg::Student => Role::Student,
+            RoleArg::Teacher => Role::Teacher,
+            RoleArg::Admin => Role::Admin,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> CliResult<()> {
+    dotenv().ok();
+    let cli = Cli::parse();
+
+    let db_url = dotenvy::var("DATABASE_URL")?;
+    let db: DatabaseConnection = Database::connect(db_url).await?;
+    let config = Config::from_env();
+
+    match cli.command {
+        Command::CreateAdmin {
+            username,
+            email,
+            name,
+            last_name,
+            school,
+        } => create_admin(&db, &config, username, email, name, last_name, school).await,
+        Command::SetRole { user, role } => set_role(&db, &user, role.into()).await,
+        Command::ResetPassword { user } => reset_password(&db, &config, &user).await,
+        Command::ListRooms => list_rooms(&db).await,
+        Command::Migrate { direction } => match direction {
+            Migrate::Up { steps } => Ok(Migrator::up(&db, steps).await?),
+            Migrate::Down { steps } => Ok(Migrator::down(&db, Some(steps)).await?),
+            Migrate::Status => Ok(Migrator::status(&db).await?),
+        },
+        Command::SeedAchievements => seed_achievements(&db).await,
+    }
+}
+
+/// Hashes `password` with the same Argon2 parameters as the server.
+fn hash_password(config: &Config, password: &str) -> CliResult<String> {
+    Ok(auth::hash_password(password, &config.argon2).map_err(|err| err.message)?)
+}
+
+/// Asks for a new password for `username`, holding it to the same policy as the API.
+fn prompt_password(config: &Config, username: &str, email: &str) -> CliResult<String> {
+    let password = rpassword::prompt_password("New password: ")?;
+    config
+        .password_policy
+        .check(&password, username, email)
+        .map_err(|err| err.message)?;
+    if rpassword::prompt_password("Repeat password: ")? != password {
+        return Err("passwords do not match".into());
+    }
+    Ok(password)
+}
+
+/// Finds a user by email or username.
+async fn find_user(db: &DatabaseConnection, user: &str) -> CliResult<user::Model> {
+    User::find()
+        .filter(
+            Condition::any()
+                .add(user::Column::Email.eq(user))
+                .add(user::Column::Username.eq(user)),
+        )
+        .one(db)
+        .await?
+        .ok_or_else(|| format!("no user `{}`", user).into())
+}
+
+async fn create_admin(
+    db: &DatabaseConnection,
+    config: &Config,
+    username: String,
+    email: String,
+    name: String,
+    last_name: String,
+    school: String,
+) -> CliResult<()> {
+    let password = prompt_password(config, &username, &email)?;
+    let password_hash = hash_password(config, &password)?;
+    let now = Utc::now().naive_utc();
+
+    let user: user::Model = user::ActiveModel {
+        username: Set(username),
+        email: Set(email),
+        // Whoever runs this has access to the database; there is nobody to mail.
+        email_verified_at: Set(Some(now)),
+        password_hash: Set(password_hash),
+        created_at: Set(now),
+        updated_at: Set(now),
+        role: Set(Role::Admin),
+        name: Set(name),
+        last_name: Set(last_name),
+        school: Set(school),
+        class: Set(String::new()),
+        score: Set(0),
+        avatar_url: Set(None),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    println!("created admin {} (id {})", user.username, user.id);
+    Ok(())
+}
+
+async fn set_role(db: &DatabaseConnection, user: &str, role: Role) -> CliResult<()> {
+    let user = find_user(db, user).await?;
+
+    user::ActiveModel {
+        id: Set(user.id),
+        role: Set(role),
+        updated_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+
+    // The role is carried in access tokens. Ending the sessions stops them from being
+    // refreshed, but tokens already issued keep the old role until they expire.
+    session::revoke_all(db, user.id).await?;
+
+    println!("{} is now {:?}", user.username, role);
+    Ok(())
+}
+
+async fn reset_password(db: &DatabaseConnection, config: &Config, user: &str) -> CliResult<()> {
+    let user = find_user(db, user).await?;
+    let password = prompt_password(config, &user.username, &user.email)?;
+    let password_hash = hash_password(config, &password)?;
+
+    user::ActiveModel {
+        id: Set(user.id),
+        password_hash: Set(password_hash),
+        password_change_required: Set(false),
+        updated_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .update(db)
+    .await?;
+
+    session::revoke_all(db, user.id).await?;
+
+    println!("password of {} was reset", user.username);
+    Ok(())
+}
+
+async fn list_rooms(db: &DatabaseConnection) -> CliResult<()> {
+    let rooms = Room::find()
+        .find_also_related(User)
+        .order_by_asc(room::Column::Id)
+        .all(db)
+        .await?;
+
+    println!(
+        "{:>6}  {:<30}  {:<20}  {:>7}",
+        "id", "name", "owner", "members"
+    );
+    for (room, owner) in rooms {
+        let members = UserRoom::find()
+            .filter(user_room::Column::RoomId.eq(room.id))
+            .count(db)
+            .await?;
+        let owner = owner.map(|owner| owner.username).unwrap_or_default();
+        println!(
+            "{:>6}  {:<30}  {:<20}  {:>7}",
+            room.id, room.name, owner, members
+        );
+    }
+    Ok(())
+}
+
+async fn seed_achievements(db: &DatabaseConnection) -> CliResult<()> {
+    let now = Utc::now().naive_utc();
+
+    for (title, description) in STARTER_ACHIEVEMENTS {
+        let existing = Achievment::find()
+            .filter(achievment::Column::Title.eq(*title))
+            .one(db)
+            .await?;
+        if existing.is_some() {
+            continue;
+        }
+
+        achievment::ActiveModel {
+            title: Set(title.to_string()),
+            description: Set(description.to_string()),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        println!("created achievement `{}`", title);
+    }
+    Ok(())
+}
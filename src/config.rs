@@ -0,0 +1,62 @@
+use argon2::Params;
+
+use crate::password::PasswordPolicy;
+
+/// Who has to verify their email address, and for what.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerificationPolicy {
+    /// Verification is offered but never required.
+    Optional,
+    /// Teacher and admin actions are blocked until the email is verified.
+    Teachers,
+    /// Nobody can log in until their email is verified.
+    Login,
+}
+
+/// Settings read from the environment at startup.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub email_verification: VerificationPolicy,
+    /// Shown next to the account name in authenticator apps.
+    pub totp_issuer: String,
+    pub password_policy: PasswordPolicy,
+    /// Cost of new password hashes. Older hashes are upgraded on login.
+    pub argon2: Params,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let email_verification = match dotenvy::var("EMAIL_VERIFICATION").as_deref() {
+            Ok("optional") => VerificationPolicy::Optional,
+            Ok("login") => VerificationPolicy::Login,
+            Ok("teachers") | Err(_) => VerificationPolicy::Teachers,
+            Ok(other) => panic!("unknown EMAIL_VERIFICATION policy `{}`", other),
+        };
+
+        let totp_issuer = dotenvy::var("TOTP_ISSUER").unwrap_or_else(|_| "StudleSTEM".to_string());
+
+        let argon2 = Params::new(
+            env_u32("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST),
+            env_u32("ARGON2_ITERATIONS", Params::DEFAULT_T_COST),
+            env_u32("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+            None,
+        )
+        .expect("invalid ARGON2_* parameters");
+
+        Self {
+            email_verification,
+            totp_issuer,
+            password_policy: PasswordPolicy::from_env(),
+            argon2,
+        }
+    }
+}
+
+fn env_u32(name: &str, default: u32) -> u32 {
+    match dotenvy::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a number", name)),
+        Err(_) => default,
+    }
+}
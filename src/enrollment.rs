@@ -0,0 +1,327 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use async_graphql::{Enum, Object, SimpleObject};
+use chrono::Utc;
+use email_address::EmailAddress;
+use entity::{
+    room,
+    user::{self, Entity as User, Role},
+    user_room::MemberRole,
+};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
+use serde::Deserialize;
+
+use crate::{
+    account,
+    auth::{hash_password, scoped_user, RoleGuard},
+    events::{Event, Notification, NotificationKind},
+    mailer::Email,
+    membership::{add_member, find_member, require_role, STAFF},
+    personal_token::Scope,
+    rooms::writable,
+    Context,
+};
+
+/// Keeps one upload from tying up the server hashing passwords.
+const MAX_ROWS: usize = 200;
+const PASSWORD_LENGTH: usize = 12;
+/// Lowercase only and no look-alikes, since these passwords get printed and typed in.
+const PASSWORD_ALPHABET: &[u8; 31] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+#[derive(Deserialize)]
+struct StudentRow {
+    username: String,
+    name: String,
+    last_name: String,
+    class: String,
+    email: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Enum)]
+pub enum EnrollmentStatus {
+    /// A new account was created and added to the room.
+    Created,
+    /// An existing account was added to the room and told about it.
+    Enrolled,
+    AlreadyMember,
+    Failed,
+}
+
+#[derive(SimpleObject)]
+pub struct EnrollmentRow {
+    /// Line in the CSV, counting the header as line 1.
+    pub line: i32,
+    pub username: String,
+    pub status: EnrollmentStatus,
+    pub user_id: Option<i32>,
+    /// Only set for new accounts.
+    pub temporary_password: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct EnrollmentResult {
+    pub rows: Vec<EnrollmentRow>,
+    /// Plain text listing the login of every new account, ready to print and hand out.
+    pub credentials_sheet: String,
+}
+
+fn temporary_password(length: usize) -> String {
+    let mut bytes = vec![0u8; length];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+        .iter()
+        .map(|byte| PASSWORD_ALPHABET[*byte as usize % PASSWORD_ALPHABET.len()] as char)
+        .collect()
+}
+
+fn failed(line: i32, username: String, error: String) -> EnrollmentRow {
+    EnrollmentRow {
+        line,
+        username,
+        status: EnrollmentStatus::Failed,
+        user_id: None,
+        temporary_password: None,
+        error: Some(error),
+    }
+}
+
+/// Adds the student in `row` to `room_id`, creating their account first if needed.
+async fn enroll(
+    my_ctx: &Context,
+    room: &room::Model,
+    school: &str,
+    line: i32,
+    row: StudentRow,
+) -> Result<EnrollmentRow, async_graphql::Error> {
+    if row.username.is_empty() || row.name.is_empty() || row.last_name.is_empty() {
+        return Err(async_graphql::Error::new(
+            "username, name and last_name are required".to_string(),
+        ));
+    }
+    if !EmailAddress::is_valid(&row.email) {
+        return Err(async_graphql::Error::new("Wrong email".to_string()));
+    }
+
+    let room_id = room.id;
+    let by_username: Option<user::Model> = User::find()
+        .filter(user::Column::Username.eq(row.username.clone()))
+        .one(&my_ctx.db)
+        .await?;
+    let by_email: Option<user::Model> = User::find_by_email(row.email.clone())
+        .one(&my_ctx.db)
+        .await?;
+
+    let existing = match (by_username, by_email) {
+        (Some(user), Some(other)) if user.id == other.id => Some(user),
+        (Some(_), _) => {
+            return Err(async_graphql::Error::new(
+                "this username is already taken".to_string(),
+            ))
+        }
+        (None, Some(_)) => {
+            return Err(async_graphql::Error::new(
+                "this email is already taken".to_string(),
+            ))
+        }
+        (None, None) => None,
+    };
+
+    if let Some(user) = existing {
+        if find_member(&my_ctx.db, user.id, room_id).await?.is_some() {
+            return Ok(EnrollmentRow {
+                line,
+                username: user.username,
+                status: EnrollmentStatus::AlreadyMember,
+                user_id: Some(user.id),
+                temporary_password: None,
+                error: None,
+            });
+        }
+
+        add_member(&my_ctx.db, user.id, room_id, MemberRole::Student).await?;
+        my_ctx.events.publish(Event::MemberJoined {
+            room_id,
+            user_id: user.id,
+        });
+
+        // They did not ask to join, so tell them both now and by mail in case they are
+        // not connected.
+        let message = format!("You were enrolled in {}", room.name);
+        my_ctx.events.publish(Event::Notification {
+            user_id: user.id,
+            notification: Notification::new(
+                NotificationKind::AddedToRoom,
+                Some(room_id),
+                message.clone(),
+            ),
+        });
+        if let Err(err) = my_ctx
+            .mailer
+            .send(Email {
+                to: user.email,
+                subject: message.clone(),
+                body: format!(
+                    "{}. If you did not expect this, you can leave the room.",
+                    message
+                ),
+            })
+            .await
+        {
+            tracing::error!("could not send enrollment email: {}", err);
+        }
+
+        return Ok(EnrollmentRow {
+            line,
+            username: user.username,
+            status: EnrollmentStatus::Enrolled,
+            user_id: Some(user.id),
+            temporary_password: None,
+            error: None,
+        });
+    }
+
+    let password =
+        temporary_password(PASSWORD_LENGTH.max(my_ctx.config.password_policy.min_length));
+    my_ctx
+        .config
+        .password_policy
+        .check(&password, &row.username, &row.email)?;
+    let password_hash = hash_password(&password, &my_ctx.config.argon2)?;
+
+    let naive_date_time = Utc::now().naive_utc();
+    let txn = my_ctx.db.begin().await?;
+
+    let user: user::Model = user::ActiveModel {
+        username: Set(row.username),
+        email: Set(row.email),
+        email_verified_at: Set(None),
+        password_hash: Set(password_hash),
+        password_change_required: Set(true),
+        created_at: Set(naive_date_time),
+        updated_at: Set(naive_date_time),
+        role: Set(Role::Student),
+        name: Set(row.name),
+        last_name: Set(row.last_name),
+        school: Set(school.to_string()),
+        class: Set(row.class),
+        score: Set(0),
+        avatar_url: Set(None),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    add_member(&txn, user.id, room_id, MemberRole::Student).await?;
+
+    txn.commit().await?;
+
+    my_ctx.events.publish(Event::MemberJoined {
+        room_id,
+        user_id: user.id,
+    });
+    if let Err(err) = account::send_verification(my_ctx, &user).await {
+        tracing::error!("could not send verification email: {}", err.message);
+    }
+
+    Ok(EnrollmentRow {
+        line,
+        username: user.username,
+        status: EnrollmentStatus::Created,
+        user_id: Some(user.id),
+        temporary_password: Some(password),
+        error: None,
+    })
+}
+
+fn line_of(position: Option<&csv::Position>) -> i32 {
+    position.map_or(0, |position| position.line() as i32)
+}
+
+/// One block per new account: `(full name, username, temporary password)`.
+fn credentials_sheet(room: &room::Model, accounts: &[(String, String, String)]) -> String {
+    let mut sheet = format!("{}\n\n", room.name);
+    for (full_name, username, password) in accounts {
+        sheet.push_str(&format!(
+            "{}\nUsername: {}\nTemporary password: {}\n\n",
+            full_name, username, password
+        ));
+    }
+    sheet.push_str("Change your password after your first login.\n");
+    sheet
+}
+
+#[derive(Default)]
+pub struct EnrollmentMutation;
+
+#[Object]
+impl EnrollmentMutation {
+    /// Enrolls every student listed in `csv` into `room_id`. The CSV needs a header row
+    /// with `username,name,last_name,class,email`. Rows that fail are reported and do not
+    /// stop the others.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsWrite)")]
+    async fn enroll_students(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        csv: String,
+    ) -> Result<EnrollmentResult, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let room = require_role(my_ctx, auth_user, room_id, STAFF).await?;
+        writable(&room)?;
+
+        let teacher: Option<user::Model> = User::find_by_id(auth_user.id).one(&my_ctx.db).await?;
+        let school = match teacher {
+            Some(teacher) => teacher.school,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(csv.as_bytes());
+        let headers = reader.headers()?.clone();
+
+        let records: Vec<csv::Result<csv::StringRecord>> = reader.records().collect();
+        if records.len() > MAX_ROWS {
+            return Err(async_graphql::Error::new(format!(
+                "at most {} students can be enrolled at once",
+                MAX_ROWS
+            )));
+        }
+
+        let mut results = Vec::new();
+        let mut sheet = Vec::new();
+        for record in records {
+            let (line, row) = match record {
+                Ok(record) => (
+                    line_of(record.position()),
+                    record.deserialize::<StudentRow>(Some(&headers)),
+                ),
+                Err(err) => (line_of(err.position()), Err(err)),
+            };
+            let row = match row {
+                Ok(row) => row,
+                Err(err) => {
+                    results.push(failed(line, String::new(), err.to_string()));
+                    continue;
+                }
+            };
+
+            let username = row.username.clone();
+            let full_name = format!("{} {}", row.name, row.last_name);
+            match enroll(my_ctx, &room, &school, line, row).await {
+                Ok(result) => {
+                    if let Some(password) = &result.temporary_password {
+                        sheet.push((full_name, result.username.clone(), password.clone()));
+                    }
+                    results.push(result);
+                }
+                Err(err) => results.push(failed(line, username, err.message)),
+            }
+        }
+
+        Ok(EnrollmentResult {
+            credentials_sheet: credentials_sheet(&room, &sheet),
+            rows: results,
+        })
+    }
+}
@@ -0,0 +1,286 @@
+use std::sync::Arc;
+
+use async_graphql::{Enum, SimpleObject, Subscription, Union};
+use chrono::{NaiveDateTime, Utc};
+use entity::{
+    announcement::{self, Entity as Announcement},
+    task::{self, Entity as Task},
+    user::Role,
+    user_room::{self, Entity as UserRoom},
+};
+use futures_util::{
+    future,
+    stream::{BoxStream, Stream, StreamExt},
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+use crate::{
+    auth::{current_user, scoped_user, AuthUser},
+    membership::{find_member, require_role, MEMBERS, STAFF},
+    personal_token::Scope,
+    Context,
+};
+
+/// Events sent to the in-process bus before slow subscribers start missing some.
+const CAPACITY: usize = 1024;
+
+/// Something subscribers may want to hear about. Events only carry ids, so they can be
+/// sent between replicas as they are; subscriptions load the rows themselves.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Event {
+    MemberJoined {
+        room_id: i32,
+        user_id: i32,
+    },
+    /// Also sent when an edit makes a hidden task visible to students. Tasks that become
+    /// visible because their `publish_at` passes are not announced, as nothing runs then.
+    TaskCreated {
+        room_id: i32,
+        task_id: i32,
+    },
+    AnnouncementPosted {
+        room_id: i32,
+        announcement_id: i32,
+    },
+    Notification {
+        user_id: i32,
+        notification: Notification,
+    },
+}
+
+impl Event {
+    /// The room this event happened in, if it is a room event.
+    fn room_id(&self) -> Option<i32> {
+        match self {
+            Event::MemberJoined { room_id, .. }
+            | Event::TaskCreated { room_id, .. }
+            | Event::AnnouncementPosted { room_id, .. } => Some(*room_id),
+            Event::Notification { .. } => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Enum, Serialize, Deserialize)]
+pub enum NotificationKind {
+    JoinRequestReceived,
+    JoinRequestApproved,
+    JoinRequestDeclined,
+    RoomOwnershipOffered,
+    /// Staff enrolled an existing account into a room.
+    AddedToRoom,
+    SubmissionGraded,
+    SubmissionReturned,
+}
+
+#[derive(Clone, Debug, SimpleObject, Serialize, Deserialize)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub room_id: Option<i32>,
+    pub message: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl Notification {
+    pub fn new(kind: NotificationKind, room_id: Option<i32>, message: String) -> Self {
+        Self {
+            kind,
+            room_id,
+            message,
+            created_at: Utc::now().naive_utc(),
+        }
+    }
+}
+
+/// Carries events from the resolver that caused them to every open subscription.
+pub trait EventBus: Send + Sync {
+    /// Sends `event` to current subscribers. Nobody listening is not an error.
+    fn publish(&self, event: Event);
+    fn subscribe(&self) -> BoxStream<'static, Event>;
+}
+
+/// Delivers events within this process only, so subscribers on other replicas miss them.
+pub struct MemoryBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for MemoryBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+}
+
+impl EventBus for MemoryBus {
+    fn publish(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    fn subscribe(&self) -> BoxStream<'static, Event> {
+        BroadcastStream::new(self.sender.subscribe())
+            .filter_map(|event| async move {
+                match event {
+                    Ok(event) => Some(event),
+                    Err(err) => {
+                        tracing::warn!("subscriber fell behind: {}", err);
+                        None
+                    }
+                }
+            })
+            .boxed()
+    }
+}
+
+/// Only `EVENT_BUS=memory` exists for now.
+pub fn from_env() -> Arc<dyn EventBus> {
+    match dotenvy::var("EVENT_BUS").as_deref() {
+        Ok("memory") | Err(_) => Arc::new(MemoryBus::default()),
+        Ok(other) => panic!("unknown EVENT_BUS `{}`", other),
+    }
+}
+
+/// Notifies the owner and co-teachers of `room_id`.
+pub async fn notify_staff(
+    my_ctx: &Context,
+    room_id: i32,
+    notification: Notification,
+) -> Result<(), DbErr> {
+    let staff: Vec<user_room::Model> = UserRoom::find()
+        .filter(user_room::Column::RoomId.eq(room_id))
+        .filter(user_room::Column::Role.is_in(STAFF.to_vec()))
+        .all(&my_ctx.db)
+        .await?;
+
+    for member in staff {
+        my_ctx.events.publish(Event::Notification {
+            user_id: member.user_id,
+            notification: notification.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Something that happened in a room.
+#[derive(Union)]
+pub enum RoomEvent {
+    MemberJoined(user_room::Model),
+    TaskCreated(task::Model),
+    AnnouncementPosted(announcement::Model),
+}
+
+/// Loads what `event` is about. Notifications are not room events.
+async fn room_event(db: &DatabaseConnection, event: Event) -> Result<Option<RoomEvent>, DbErr> {
+    let event = match event {
+        Event::MemberJoined { room_id, user_id } => find_member(db, user_id, room_id)
+            .await?
+            .map(RoomEvent::MemberJoined),
+        Event::TaskCreated { task_id, .. } => Task::find_by_id(task_id)
+            .one(db)
+            .await?
+            .map(RoomEvent::TaskCreated),
+        Event::AnnouncementPosted {
+            announcement_id, ..
+        } => Announcement::find_by_id(announcement_id)
+            .one(db)
+            .await?
+            .map(RoomEvent::AnnouncementPosted),
+        Event::Notification { .. } => None,
+    };
+    Ok(event)
+}
+
+/// Ends `stream` once the token of `user` expires, so a socket does not keep streaming
+/// to someone who has logged out or lost their role since connecting.
+fn until_expired<S: Stream>(stream: S, user: &AuthUser) -> impl Stream<Item = S::Item> {
+    let expires_at = user.expires_at;
+    stream.take_until(async move {
+        match expires_at {
+            Some(expires_at) => {
+                let left = expires_at - Utc::now().naive_utc();
+                tokio::time::sleep(left.to_std().unwrap_or_default()).await;
+            }
+            None => future::pending().await,
+        }
+    })
+}
+
+#[derive(Default)]
+pub struct EventSubscription;
+
+#[Subscription]
+impl EventSubscription {
+    /// Members joining, tasks being created and announcements being posted in `room_id`.
+    /// Scheduled tasks are not announced when their `publish_at` passes.
+    async fn room_events(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<impl Stream<Item = RoomEvent>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+        require_role(my_ctx, auth_user, room_id, MEMBERS).await?;
+
+        let db = my_ctx.db.clone();
+        let user_id = auth_user.id;
+        let admin = auth_user.role == Role::Admin;
+
+        let events = my_ctx.events.subscribe().filter_map(move |event| {
+            let db = db.clone();
+            async move {
+                // Everything on the bus passes through here, so skip other rooms before
+                // touching the database.
+                if event.room_id() != Some(room_id) {
+                    return None;
+                }
+
+                // Stop delivering to members who were removed after subscribing.
+                let member = if admin {
+                    Ok(true)
+                } else {
+                    find_member(&db, user_id, room_id)
+                        .await
+                        .map(|member| member.is_some())
+                };
+                let result = match member {
+                    Ok(true) => room_event(&db, event).await,
+                    Ok(false) => Ok(None),
+                    Err(err) => Err(err),
+                };
+                match result {
+                    Ok(event) => event,
+                    Err(err) => {
+                        tracing::error!("could not load room event: {}", err);
+                        None
+                    }
+                }
+            }
+        });
+        Ok(until_expired(events, auth_user))
+    }
+
+    /// Notifications meant for the current user.
+    async fn my_notifications(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<impl Stream<Item = Notification>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+        let user_id = auth_user.id;
+
+        let notifications = my_ctx
+            .events
+            .subscribe()
+            .filter_map(move |event| async move {
+                match event {
+                    Event::Notification {
+                        user_id: recipient,
+                        notification,
+                    } if recipient == user_id => Some(notification),
+                    _ => None,
+                }
+            });
+        Ok(until_expired(notifications, auth_user))
+    }
+}
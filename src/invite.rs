@@ -0,0 +1,237 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use async_graphql::Object;
+use chrono::{Duration, Utc};
+use entity::{
+    room::{self, Entity as Room},
+    room_invite::{self, Entity as RoomInvite},
+    user::Role,
+    user_room::MemberRole,
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+    QueryFilter, Set, TransactionTrait,
+};
+
+use crate::{
+    auth::{current_user, scoped_user, RoleGuard},
+    events::Event,
+    membership::{add_member, find_member, require_role, STAFF},
+    personal_token::Scope,
+    rooms::writable,
+    Context,
+};
+
+const CODE_LENGTH: usize = 8;
+/// A school year; rotate the code for anything longer.
+const MAX_EXPIRES_IN_HOURS: i64 = 24 * 366;
+/// No 0/O or 1/I, so codes survive being read out loud or copied off a board.
+const CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+fn new_code() -> String {
+    let mut bytes = [0u8; CODE_LENGTH];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+        .iter()
+        .map(|byte| CODE_ALPHABET[*byte as usize % CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+/// Invites that are neither revoked nor expired. Use limits are checked separately.
+fn active() -> Condition {
+    Condition::all()
+        .add(room_invite::Column::RevokedAt.is_null())
+        .add(
+            Condition::any()
+                .add(room_invite::Column::ExpiresAt.is_null())
+                .add(room_invite::Column::ExpiresAt.gt(Utc::now().naive_utc())),
+        )
+}
+
+async fn revoke_all<C: ConnectionTrait>(db: &C, room_id: i32) -> Result<u64, DbErr> {
+    let revoked = RoomInvite::update_many()
+        .col_expr(
+            room_invite::Column::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(room_invite::Column::RoomId.eq(room_id))
+        .filter(room_invite::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+    Ok(revoked.rows_affected)
+}
+
+#[derive(Default)]
+pub struct InviteQuery;
+
+#[Object]
+impl InviteQuery {
+    /// The invite code currently accepted for `room_id`, if there is one.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsRead)")]
+    async fn room_invite(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<Option<room_invite::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+        require_role(my_ctx, auth_user, room_id, STAFF).await?;
+
+        let invite: Option<room_invite::Model> = RoomInvite::find()
+            .filter(room_invite::Column::RoomId.eq(room_id))
+            .filter(active())
+            .one(&my_ctx.db)
+            .await?;
+        Ok(invite)
+    }
+}
+
+#[derive(Default)]
+pub struct InviteMutation;
+
+#[Object]
+impl InviteMutation {
+    /// Replaces the invite code of `room_id`. The old code stops working immediately.
+    /// Only the owner manages who can join with a code.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsWrite)")]
+    async fn rotate_invite_code(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        expires_in_hours: Option<i64>,
+        max_uses: Option<i32>,
+    ) -> Result<room_invite::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let room = require_role(my_ctx, auth_user, room_id, &[MemberRole::Owner]).await?;
+        writable(&room)?;
+
+        if expires_in_hours.is_some_and(|hours| !(1..=MAX_EXPIRES_IN_HOURS).contains(&hours)) {
+            return Err(async_graphql::Error::new(format!(
+                "expiresInHours must be between 1 and {}",
+                MAX_EXPIRES_IN_HOURS
+            )));
+        }
+        if max_uses.is_some_and(|uses| uses <= 0) {
+            return Err(async_graphql::Error::new(
+                "maxUses must be positive".to_string(),
+            ));
+        }
+
+        let now = Utc::now().naive_utc();
+        let expires_at = expires_in_hours.map(|hours| now + Duration::hours(hours));
+
+        let txn = my_ctx.db.begin().await?;
+
+        revoke_all(&txn, room_id).await?;
+
+        let invite = room_invite::ActiveModel {
+            room_id: Set(room_id),
+            code: Set(new_code()),
+            created_by: Set(auth_user.id),
+            max_uses: Set(max_uses),
+            uses: Set(0),
+            created_at: Set(now),
+            expires_at: Set(expires_at),
+            revoked_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        txn.commit().await?;
+        Ok(invite)
+    }
+
+    /// Stops the current invite code of `room_id` from working without issuing a new one.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsWrite)")]
+    async fn revoke_invite_code(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        require_role(my_ctx, auth_user, room_id, &[MemberRole::Owner]).await?;
+
+        Ok(revoke_all(&my_ctx.db, room_id).await? > 0)
+    }
+
+    /// Adds the current user to the room `code` belongs to.
+    async fn join_room(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        code: String,
+    ) -> Result<room::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let invite: Option<room_invite::Model> = RoomInvite::find()
+            .filter(room_invite::Column::Code.eq(code.trim().to_uppercase()))
+            .filter(active())
+            .one(&my_ctx.db)
+            .await?;
+
+        let invite = match invite {
+            Some(invite) => invite,
+            None => {
+                return Err(async_graphql::Error::new(
+                    "this invite code is not valid".to_string(),
+                ))
+            }
+        };
+
+        let room: Option<room::Model> = Room::find_by_id(invite.room_id).one(&my_ctx.db).await?;
+
+        let room = match room {
+            Some(room) => room,
+            None => return Err(async_graphql::Error::new("room not found".to_string())),
+        };
+        writable(&room)?;
+
+        if find_member(&my_ctx.db, auth_user.id, invite.room_id)
+            .await?
+            .is_some()
+        {
+            return Err(async_graphql::Error::new(
+                "you are already in this room".to_string(),
+            ));
+        }
+
+        let txn = my_ctx.db.begin().await?;
+
+        // Counting the use in the same statement that checks the limit keeps
+        // concurrent joins from going over it.
+        let used = RoomInvite::update_many()
+            .col_expr(
+                room_invite::Column::Uses,
+                Expr::col(room_invite::Column::Uses).add(1),
+            )
+            .filter(room_invite::Column::Id.eq(invite.id))
+            .filter(active())
+            .filter(
+                Condition::any()
+                    .add(room_invite::Column::MaxUses.is_null())
+                    .add(
+                        Expr::col(room_invite::Column::Uses)
+                            .lt(Expr::col(room_invite::Column::MaxUses)),
+                    ),
+            )
+            .exec(&txn)
+            .await?;
+        if used.rows_affected == 0 {
+            return Err(async_graphql::Error::new(
+                "this invite code is not valid".to_string(),
+            ));
+        }
+
+        add_member(&txn, auth_user.id, invite.room_id, MemberRole::Student).await?;
+        txn.commit().await?;
+
+        my_ctx.events.publish(Event::MemberJoined {
+            room_id: room.id,
+            user_id: auth_user.id,
+        });
+
+        Ok(room)
+    }
+}
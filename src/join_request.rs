@@ -0,0 +1,226 @@
+use async_graphql::Object;
+use chrono::Utc;
+use entity::{
+    room::{self, Entity as Room},
+    room_join_request::{self, Entity as RoomJoinRequest, JoinRequestStatus},
+    user_room::MemberRole,
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+
+use crate::{
+    auth::{current_user, scoped_user},
+    events::{self, Event, Notification, NotificationKind},
+    membership::{add_member, find_member, require_role, STAFF},
+    personal_token::Scope,
+    rooms::writable,
+    Context,
+};
+
+/// Moves a pending request to `status` on behalf of the room's staff. Approving also
+/// adds the requester to the room as a student.
+async fn review(
+    ctx: &async_graphql::Context<'_>,
+    request_id: i32,
+    status: JoinRequestStatus,
+) -> Result<room_join_request::Model, async_graphql::Error> {
+    let my_ctx = ctx.data::<Context>().unwrap();
+    let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+
+    let request: Option<room_join_request::Model> = RoomJoinRequest::find_by_id(request_id)
+        .one(&my_ctx.db)
+        .await?;
+
+    let request = match request {
+        Some(request) => request,
+        None => {
+            return Err(async_graphql::Error::new(
+                "there is no pending join request with this id".to_string(),
+            ))
+        }
+    };
+
+    let room = require_role(my_ctx, auth_user, request.room_id, STAFF).await?;
+    if status == JoinRequestStatus::Approved {
+        writable(&room)?;
+    }
+
+    let now = Utc::now().naive_utc();
+    let txn = my_ctx.db.begin().await?;
+
+    let reviewed = RoomJoinRequest::update_many()
+        .col_expr(room_join_request::Column::Status, Expr::value(status))
+        .col_expr(
+            room_join_request::Column::ReviewedBy,
+            Expr::value(auth_user.id),
+        )
+        .col_expr(room_join_request::Column::ReviewedAt, Expr::value(now))
+        .filter(room_join_request::Column::Id.eq(request_id))
+        .filter(room_join_request::Column::Status.eq(JoinRequestStatus::Pending))
+        .exec(&txn)
+        .await?;
+
+    if reviewed.rows_affected == 0 {
+        return Err(async_graphql::Error::new(
+            "there is no pending join request with this id".to_string(),
+        ));
+    }
+
+    // The student may have joined with an invite code in the meantime.
+    let joined = status == JoinRequestStatus::Approved
+        && find_member(&txn, request.user_id, request.room_id)
+            .await?
+            .is_none();
+    if joined {
+        add_member(&txn, request.user_id, request.room_id, MemberRole::Student).await?;
+    }
+
+    txn.commit().await?;
+
+    if joined {
+        my_ctx.events.publish(Event::MemberJoined {
+            room_id: request.room_id,
+            user_id: request.user_id,
+        });
+    }
+    let (kind, message) = match status {
+        JoinRequestStatus::Approved => (
+            NotificationKind::JoinRequestApproved,
+            format!("You were let into {}", room.name),
+        ),
+        _ => (
+            NotificationKind::JoinRequestDeclined,
+            format!("Your request to join {} was declined", room.name),
+        ),
+    };
+    my_ctx.events.publish(Event::Notification {
+        user_id: request.user_id,
+        notification: Notification::new(kind, Some(request.room_id), message),
+    });
+
+    Ok(room_join_request::Model {
+        status,
+        reviewed_by: Some(auth_user.id),
+        reviewed_at: Some(now),
+        ..request
+    })
+}
+
+#[derive(Default)]
+pub struct JoinRequestQuery;
+
+#[Object]
+impl JoinRequestQuery {
+    /// Pending requests to join `room_id`, oldest first.
+    async fn join_requests(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<Vec<room_join_request::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+        require_role(my_ctx, auth_user, room_id, STAFF).await?;
+
+        let requests: Vec<room_join_request::Model> = RoomJoinRequest::find()
+            .filter(room_join_request::Column::RoomId.eq(room_id))
+            .filter(room_join_request::Column::Status.eq(JoinRequestStatus::Pending))
+            .order_by_asc(room_join_request::Column::CreatedAt)
+            .all(&my_ctx.db)
+            .await?;
+        Ok(requests)
+    }
+}
+
+#[derive(Default)]
+pub struct JoinRequestMutation;
+
+#[Object]
+impl JoinRequestMutation {
+    /// Asks the staff of `room_id` to let the current user in.
+    async fn request_to_join(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        message: Option<String>,
+    ) -> Result<room_join_request::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let room: Option<room::Model> = Room::find_by_id(room_id).one(&my_ctx.db).await?;
+
+        let room = match room {
+            Some(room) => room,
+            None => return Err(async_graphql::Error::new("room not found".to_string())),
+        };
+        writable(&room)?;
+
+        if find_member(&my_ctx.db, auth_user.id, room_id)
+            .await?
+            .is_some()
+        {
+            return Err(async_graphql::Error::new(
+                "you are already in this room".to_string(),
+            ));
+        }
+
+        let pending: Option<room_join_request::Model> = RoomJoinRequest::find()
+            .filter(room_join_request::Column::RoomId.eq(room_id))
+            .filter(room_join_request::Column::UserId.eq(auth_user.id))
+            .filter(room_join_request::Column::Status.eq(JoinRequestStatus::Pending))
+            .one(&my_ctx.db)
+            .await?;
+        if pending.is_some() {
+            return Err(async_graphql::Error::new(
+                "you already asked to join this room".to_string(),
+            ));
+        }
+
+        let message = message
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty());
+
+        let request = room_join_request::ActiveModel {
+            room_id: Set(room_id),
+            user_id: Set(auth_user.id),
+            message: Set(message),
+            status: Set(JoinRequestStatus::Pending),
+            reviewed_by: Set(None),
+            created_at: Set(Utc::now().naive_utc()),
+            reviewed_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&my_ctx.db)
+        .await?;
+
+        let notification = Notification::new(
+            NotificationKind::JoinRequestReceived,
+            Some(room_id),
+            format!("Someone asked to join {}", room.name),
+        );
+        if let Err(err) = events::notify_staff(my_ctx, room_id, notification).await {
+            tracing::error!("could not notify room staff: {}", err);
+        }
+
+        Ok(request)
+    }
+
+    /// Lets the requester into the room as a student.
+    async fn approve_join_request(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        request_id: i32,
+    ) -> Result<room_join_request::Model, async_graphql::Error> {
+        review(ctx, request_id, JoinRequestStatus::Approved).await
+    }
+
+    /// Turns the request down. The student may ask again later.
+    async fn decline_join_request(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        request_id: i32,
+    ) -> Result<room_join_request::Model, async_graphql::Error> {
+        review(ctx, request_id, JoinRequestStatus::Declined).await
+    }
+}
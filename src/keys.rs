@@ -0,0 +1,158 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::{
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey},
+    SigningKey, VerifyingKey,
+};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+pub type KeyError = Box<dyn Error + Send + Sync>;
+
+/// Ed25519 keys used for access tokens. One private key signs new tokens; its public
+/// half and any older public keys still verify them, so keys can be rotated without
+/// logging everyone out.
+pub struct KeySet {
+    signing_kid: String,
+    signing: EncodingKey,
+    verifying: HashMap<String, DecodingKey>,
+    jwks: JwkSet,
+}
+
+impl KeySet {
+    /// Loads the PEM encoded PKCS#8 private key at `private_key` and the PEM encoded
+    /// public keys at `public_keys`.
+    pub fn load<P: AsRef<Path>>(private_key: P, public_keys: &[P]) -> Result<Self, KeyError> {
+        let signing_key = SigningKey::from_pkcs8_pem(&fs::read_to_string(private_key)?)?;
+        let signing = EncodingKey::from_ed_der(signing_key.to_pkcs8_der()?.as_bytes());
+
+        let mut keys = vec![signing_key.verifying_key()];
+        for path in public_keys {
+            keys.push(VerifyingKey::from_public_key_pem(&fs::read_to_string(
+                path,
+            )?)?);
+        }
+
+        let mut verifying = HashMap::new();
+        let mut jwks = JwkSet { keys: Vec::new() };
+        for key in &keys {
+            let jwk = public_jwk(key);
+            let kid = jwk.common.key_id.clone().unwrap_or_default();
+            if verifying.contains_key(&kid) {
+                continue;
+            }
+            verifying.insert(kid, DecodingKey::from_jwk(&jwk)?);
+            jwks.keys.push(jwk);
+        }
+
+        Ok(Self {
+            signing_kid: thumbprint(&keys[0]),
+            signing,
+            verifying,
+            jwks,
+        })
+    }
+
+    /// Reads `JWT_PRIVATE_KEY` and the comma separated `JWT_PUBLIC_KEYS`.
+    pub fn from_env() -> Self {
+        let private_key = dotenvy::var("JWT_PRIVATE_KEY")
+            .expect("JWT_PRIVATE_KEY environment variable not found");
+        let public_keys: Vec<String> = dotenvy::var("JWT_PUBLIC_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        Self::load(private_key, &public_keys)
+            .expect("could not load JWT keys; .env.example shows how to generate them")
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, KeyError> {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(self.signing_kid.clone());
+        Ok(jsonwebtoken::encode(&header, claims, &self.signing)?)
+    }
+
+    /// Checks the signature of `token` with the key named by its `kid`, and its expiration.
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T, KeyError> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let key = header
+            .kid
+            .and_then(|kid| self.verifying.get(&kid))
+            .ok_or("unknown signing key")?;
+
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.leeway = 0;
+        Ok(jsonwebtoken::decode(token, key, &validation)?.claims)
+    }
+
+    /// The public keys, as served on `/.well-known/jwks.json`.
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+fn public_jwk(key: &VerifyingKey) -> Jwk {
+    Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(KeyAlgorithm::EdDSA),
+            key_id: Some(thumbprint(key)),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(key.as_bytes()),
+        }),
+    }
+}
+
+/// RFC 7638 thumbprint of `key`, used as its `kid`.
+fn thumbprint(key: &VerifyingKey) -> String {
+    let canonical = format!(
+        r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#,
+        URL_SAFE_NO_PAD.encode(key.as_bytes())
+    );
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Ed25519 public key from RFC 8037, appendix A.2.
+    fn rfc8037_key() -> VerifyingKey {
+        let x = URL_SAFE_NO_PAD
+            .decode("11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo")
+            .unwrap();
+        VerifyingKey::from_bytes(&x.try_into().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn thumbprint_matches_rfc8037() {
+        // RFC 8037, appendix A.3.
+        assert_eq!(
+            thumbprint(&rfc8037_key()),
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
+    }
+
+    #[test]
+    fn jwk_is_named_by_its_thumbprint() {
+        let jwk = public_jwk(&rfc8037_key());
+        assert_eq!(
+            jwk.common.key_id.as_deref(),
+            Some("kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k")
+        );
+    }
+}
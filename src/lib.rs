@@ -0,0 +1,722 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use async_graphql::{
+    http::GraphiQLSource, MergedObject, MergedSubscription, Object, Schema, SimpleObject, Union,
+};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
+use auth::{
+    hash_password, needs_rehash, scoped_user, verify_password, AuthUser, Claims, ClientInfo,
+    RoleGuard, TokenVerifier,
+};
+use chrono::{NaiveDateTime, Utc};
+use config::{Config, VerificationPolicy};
+use email_address::EmailAddress;
+use entity::{
+    achievment::{self, Entity as Achievment},
+    room::{self, Entity as Room},
+    task::{self, Entity as Task, TaskStatus},
+    user::{self, Entity as User, Role},
+    user_achievment::{self, Entity as UserAchievment},
+    user_room::{self, Entity as UserRoom, MemberRole},
+};
+use events::{Event, EventBus};
+use keys::KeySet;
+use mailer::Mailer;
+use personal_token::Scope;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait, TryIntoModel,
+};
+use std::sync::Arc;
+use throttle::AttemptStore;
+
+pub mod account;
+pub mod announcements;
+pub mod auth;
+pub mod config;
+pub mod enrollment;
+pub mod events;
+pub mod invite;
+pub mod join_request;
+pub mod keys;
+pub mod mailer;
+pub mod membership;
+pub mod ownership;
+pub mod password;
+pub mod personal_token;
+pub mod rooms;
+pub mod session;
+pub mod submissions;
+pub mod tasks;
+pub mod teacher;
+pub mod throttle;
+pub mod totp;
+
+const ACCESS_EXPIRATION: usize = 100;
+
+pub type AppSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+pub async fn index(
+    schema: web::Data<AppSchema>,
+    verifier: web::Data<TokenVerifier>,
+    req: HttpRequest,
+    gql_request: GraphQLRequest,
+) -> GraphQLResponse {
+    let mut request = gql_request
+        .into_inner()
+        .data(ClientInfo::from_request(&req));
+    if let Some(user) = verifier.authenticate(&req).await {
+        request = request.data(user);
+    }
+    schema.execute(request).await.into()
+}
+
+/// Serves subscriptions over graphql-ws. Clients authenticate with the upgrade request's
+/// `Authorization` header or in their `connection_init` payload. Each subscription ends
+/// when the token it was started with expires.
+pub async fn index_ws(
+    schema: web::Data<AppSchema>,
+    verifier: web::Data<TokenVerifier>,
+    req: HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse> {
+    let mut data = async_graphql::Data::default();
+    if let Some(user) = verifier.authenticate(&req).await {
+        data.insert(user);
+    }
+
+    let verifier = verifier.get_ref().clone();
+    GraphQLSubscription::new(Schema::clone(&*schema))
+        .with_data(data)
+        .on_connection_init(move |payload| async move { verifier.connection_init(payload).await })
+        .start(&req, payload)
+}
+
+pub async fn index_graphiql() -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(
+            GraphiQLSource::build()
+                .endpoint("/")
+                .subscription_endpoint("/")
+                .finish(),
+        ))
+}
+
+/// Public keys other services use to check our access tokens.
+pub async fn jwks(keys: web::Data<KeySet>) -> HttpResponse {
+    HttpResponse::Ok().json(keys.jwks())
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "LoginResponse")]
+pub struct LoginResponse {
+    refresh_token: String,
+    access_token: String,
+}
+
+/// What `login` returns: tokens, or a challenge when a TOTP code is still needed.
+#[derive(Union)]
+pub enum LoginResult {
+    Tokens(LoginResponse),
+    TotpChallenge(totp::TotpChallenge),
+}
+
+pub struct Context {
+    db: DatabaseConnection,
+    keys: Arc<KeySet>,
+    refr_key: String,
+    mailer: Arc<dyn Mailer>,
+    attempts: Arc<dyn AttemptStore>,
+    events: Arc<dyn EventBus>,
+    config: Config,
+}
+
+impl Context {
+    pub fn new(
+        db: DatabaseConnection,
+        keys: Arc<KeySet>,
+        refr_key: String,
+        mailer: Arc<dyn Mailer>,
+        attempts: Arc<dyn AttemptStore>,
+        events: Arc<dyn EventBus>,
+        config: Config,
+    ) -> Self {
+        Self {
+            db,
+            keys,
+            refr_key,
+            mailer,
+            attempts,
+            events,
+            config,
+        }
+    }
+
+    /// Signs an access token for `user` to go along with `refresh_token`.
+    fn issue_tokens(
+        &self,
+        user: &user::Model,
+        refresh_token: String,
+    ) -> Result<LoginResponse, async_graphql::Error> {
+        let access_token = Claims::new(user, ACCESS_EXPIRATION).sign(&self.keys)?;
+
+        Ok(LoginResponse {
+            refresh_token,
+            access_token,
+        })
+    }
+}
+
+#[derive(MergedObject, Default)]
+pub struct QueryRoot(
+    BaseQuery,
+    session::SessionQuery,
+    throttle::ThrottleQuery,
+    teacher::TeacherQuery,
+    personal_token::PersonalTokenQuery,
+    invite::InviteQuery,
+    membership::MembershipQuery,
+    join_request::JoinRequestQuery,
+    ownership::OwnershipQuery,
+    rooms::RoomQuery,
+    announcements::AnnouncementQuery,
+    submissions::SubmissionQuery,
+);
+
+#[derive(Default)]
+pub struct BaseQuery;
+
+#[Object]
+impl BaseQuery {
+    async fn me(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<user::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::ProfileRead)?;
+        let user: Option<user::Model> = User::find_by_id(auth_user.id).one(&my_ctx.db).await?;
+
+        let mut user = match user {
+            Some(user) => user,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        let user_achievments: Vec<user_achievment::Model> =
+            user.find_related(UserAchievment).all(&my_ctx.db).await?;
+
+        let ids: Vec<i32> = user_achievments
+            .iter()
+            .map(|achievment| achievment.achievment_id)
+            .collect();
+
+        let achs: Vec<achievment::Model> = Achievment::find()
+            .filter(achievment::Column::Id.is_in(ids))
+            .all(&my_ctx.db)
+            .await?;
+
+        let user_rooms: Vec<user_room::Model> = user.find_related(UserRoom).all(&my_ctx.db).await?;
+
+        let ids: Vec<i32> = user_rooms.iter().map(|room| room.room_id).collect();
+
+        let rooms: Vec<room::Model> = Room::find()
+            .filter(room::Column::Id.is_in(ids))
+            .filter(room::Column::ArchivedAt.is_null())
+            .all(&my_ctx.db)
+            .await?;
+
+        user.achievments = achs;
+        user.rooms = rooms;
+
+        Ok(user)
+    }
+
+    async fn get_task(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+    ) -> Result<task::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let task: Option<task::Model> = Task::find_by_id(id).one(&my_ctx.db).await?;
+
+        let task = match task {
+            Some(task) => task,
+            None => return Err(async_graphql::Error::new("task not found".to_string())),
+        };
+
+        // Drafts and scheduled tasks are only shown to the room's staff.
+        if !tasks::is_visible(&task) {
+            let staff = match ctx.data_opt::<AuthUser>() {
+                Some(user) if !user.password_change_required => {
+                    membership::is_staff(my_ctx, user, task.room_id).await?
+                }
+                _ => false,
+            };
+            if !staff {
+                return Err(async_graphql::Error::new("task not found".to_string()));
+            }
+        }
+
+        Ok(task)
+    }
+
+    async fn get_user(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+    ) -> Result<user::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let user: Option<user::Model> = User::find_by_id(id).one(&my_ctx.db).await?;
+
+        let mut user = match user {
+            Some(user) => user,
+            None => return Err(async_graphql::Error::new("user not found".to_string())),
+        };
+
+        let achievments: Option<Vec<user_achievment::Model>> = Some(
+            UserAchievment::find()
+                .filter(user_achievment::Column::UserId.eq(id))
+                .all(&my_ctx.db)
+                .await?,
+        );
+
+        let achievments = match achievments {
+            Some(achievments) => achievments,
+            None => return Err(async_graphql::Error::new("room not found".to_string())),
+        };
+
+        let ids: Vec<i32> = achievments
+            .iter()
+            .map(|achievment| achievment.achievment_id)
+            .collect();
+
+        let achs: Option<Vec<achievment::Model>> = Some(
+            Achievment::find()
+                .filter(achievment::Column::Id.is_in(ids))
+                .all(&my_ctx.db)
+                .await?,
+        );
+
+        let achs = match achs {
+            Some(achs) => achs,
+            None => return Err(async_graphql::Error::new("user not found".to_string())),
+        };
+
+        user.achievments = achs;
+
+        Ok(user)
+    }
+
+    async fn get_room(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<room::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+
+        let rooms: Vec<user_room::Model> = UserRoom::find()
+            .filter(user_room::Column::UserId.eq(auth_user.id))
+            .all(&my_ctx.db)
+            .await?;
+
+        let ids: Vec<i32> = rooms.iter().map(|room| room.room_id).collect();
+
+        if !ids.contains(&room_id) {
+            return Err(async_graphql::Error::new(
+                "you do not exist in this room".to_string(),
+            ));
+        }
+
+        let rooms: Vec<user_room::Model> = UserRoom::find()
+            .filter(user_room::Column::RoomId.eq(room_id))
+            .all(&my_ctx.db)
+            .await?;
+
+        let ids: Vec<i32> = rooms.iter().map(|room| room.user_id).collect();
+
+        let users: Vec<user::Model> = User::find()
+            .filter(user::Column::Id.is_in(ids))
+            .all(&my_ctx.db)
+            .await?;
+
+        let mut tasks = Task::find().filter(task::Column::RoomId.eq(room_id));
+        if !membership::is_staff(my_ctx, auth_user, room_id).await? {
+            tasks = tasks.filter(tasks::visible());
+        }
+        let tasks: Vec<task::Model> = tasks
+            .order_by_asc(task::Column::CreatedAt)
+            .all(&my_ctx.db)
+            .await?;
+
+        let room: Option<room::Model> = Room::find_by_id(room_id).one(&my_ctx.db).await?;
+
+        let mut room = match room {
+            Some(room) => room,
+            None => return Err(async_graphql::Error::new("room not found".to_string())),
+        };
+
+        room.users = users;
+        room.tasks = tasks;
+        let unread = announcements::unread_counts(&my_ctx.db, auth_user.id, &[room.id]).await?;
+        room.unread_announcements = Some(unread.get(&room.id).copied().unwrap_or(0));
+
+        Ok(room)
+    }
+}
+
+#[derive(MergedSubscription, Default)]
+pub struct SubscriptionRoot(events::EventSubscription);
+
+#[derive(MergedObject, Default)]
+pub struct MutationRoot(
+    BaseMutation,
+    session::SessionMutation,
+    account::AccountMutation,
+    throttle::ThrottleMutation,
+    totp::TotpMutation,
+    teacher::TeacherMutation,
+    personal_token::PersonalTokenMutation,
+    invite::InviteMutation,
+    membership::MembershipMutation,
+    rooms::RoomMutation,
+    join_request::JoinRequestMutation,
+    ownership::OwnershipMutation,
+    enrollment::EnrollmentMutation,
+    announcements::AnnouncementMutation,
+    tasks::TaskMutation,
+    submissions::SubmissionMutation,
+);
+
+#[derive(Default)]
+pub struct BaseMutation;
+
+#[Object]
+impl BaseMutation {
+    #[allow(clippy::too_many_arguments)]
+    async fn register(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        username: String,
+        email: String,
+        password: String,
+        role: Role,
+        name: String,
+        last_name: String,
+        school: String,
+        class: String,
+        teacher_application: Option<teacher::TeacherApplicationInput>,
+    ) -> Result<user::Model, async_graphql::Error> {
+        if role == Role::Admin {
+            return Err(async_graphql::Error::new("Something is wrong".to_string()));
+        }
+
+        if role == Role::Teacher && teacher_application.is_none() {
+            return Err(async_graphql::Error::new(
+                "teacher signups need a teacherApplication".to_string(),
+            ));
+        }
+
+        if !EmailAddress::is_valid(&email) {
+            return Err(async_graphql::Error::new("Wrong email".to_string()));
+        }
+
+        let my_ctx = ctx.data::<Context>().unwrap();
+        my_ctx
+            .config
+            .password_policy
+            .check(&password, &username, &email)?;
+        let password_hash = hash_password(&password, &my_ctx.config.argon2)?;
+
+        let naive_date_time = Utc::now().naive_utc();
+
+        let user = user::ActiveModel {
+            username: Set(username),
+            email: Set(email),
+            email_verified_at: Set(None),
+            password_hash: Set(password_hash),
+            created_at: Set(naive_date_time),
+            updated_at: Set(naive_date_time),
+            // Teachers start out as students until an admin approves their application.
+            role: Set(Role::Student),
+            name: Set(name),
+            last_name: Set(last_name),
+            school: Set(school.clone()),
+            class: Set(class),
+            score: Set(0),
+            avatar_url: Set(None),
+            ..Default::default()
+        };
+
+        let user: user::Model = user.insert(&my_ctx.db).await?;
+
+        if let Some(application) = teacher_application {
+            teacher::submit(my_ctx, &user, school, application).await?;
+        }
+
+        // The account exists either way; a lost mail can be re-requested.
+        if let Err(err) = account::send_verification(my_ctx, &user).await {
+            tracing::error!("could not send verification email: {}", err.message);
+        }
+
+        Ok(user)
+    }
+
+    async fn refresh(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        refresh_token: String,
+    ) -> Result<LoginResponse, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let client = ctx.data::<ClientInfo>()?;
+
+        let (user_id, refresh_token) =
+            session::rotate(&my_ctx.db, &my_ctx.refr_key, &refresh_token, client).await?;
+
+        let user: Option<user::Model> = User::find_by_id(user_id).one(&my_ctx.db).await?;
+
+        let user = match user {
+            Some(user) => user,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        my_ctx.issue_tokens(&user, refresh_token)
+    }
+
+    async fn login(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        email: String,
+        password: String,
+    ) -> Result<LoginResult, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let client = ctx.data::<ClientInfo>()?;
+
+        throttle::check(my_ctx.attempts.as_ref(), &email, client).await?;
+
+        let user: Option<user::Model> = User::find_by_email(email.clone()).one(&my_ctx.db).await?;
+
+        let user = match user {
+            Some(user) if verify_password(&password, &user.password_hash) => user,
+            _ => {
+                throttle::record_failure(my_ctx.attempts.as_ref(), &email, client).await?;
+                return Err(async_graphql::Error::new(
+                    "Wrong email or password".to_string(),
+                ));
+            }
+        };
+
+        // Upgrade hashes made with older Argon2 settings while we have the password.
+        if needs_rehash(&user.password_hash, &my_ctx.config.argon2) {
+            let rehashed = user::ActiveModel {
+                id: Set(user.id),
+                password_hash: Set(hash_password(&password, &my_ctx.config.argon2)?),
+                ..Default::default()
+            }
+            .update(&my_ctx.db)
+            .await;
+            if let Err(err) = rehashed {
+                tracing::error!("could not upgrade password hash: {}", err);
+            }
+        }
+
+        if my_ctx.config.email_verification == VerificationPolicy::Login
+            && user.email_verified_at.is_none()
+        {
+            return Err(async_graphql::Error::new(
+                "verify your email before logging in".to_string(),
+            ));
+        }
+
+        // The counter is only cleared once the second factor passes too, so the password
+        // alone cannot reset the lockout between rounds of TOTP guesses.
+        if user.totp_enabled_at.is_some() {
+            return Ok(LoginResult::TotpChallenge(
+                totp::challenge(my_ctx, &user).await?,
+            ));
+        }
+
+        throttle::record_success(my_ctx.attempts.as_ref(), &email).await?;
+
+        let refresh_token = session::start(&my_ctx.db, &my_ctx.refr_key, user.id, client).await?;
+        Ok(LoginResult::Tokens(
+            my_ctx.issue_tokens(&user, refresh_token)?,
+        ))
+    }
+
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsWrite)")]
+    async fn create_room(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        name: String,
+    ) -> Result<room::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+
+        let naive_date_time = Utc::now().naive_utc();
+        let room = room::ActiveModel {
+            created_at: Set(naive_date_time),
+            updated_at: Set(naive_date_time),
+            name: Set(name),
+            owner: Set(auth_user.id),
+            ..Default::default()
+        };
+
+        let txn = my_ctx.db.begin().await?;
+        let room: room::Model = room.insert(&txn).await?;
+        membership::add_member(&txn, auth_user.id, room.id, MemberRole::Owner).await?;
+        txn.commit().await?;
+
+        Ok(room)
+    }
+
+    async fn edit(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        school: Option<String>,
+        name: Option<String>,
+        last_name: Option<String>,
+        class: Option<String>,
+        avatar_url: Option<String>,
+    ) -> Result<user::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::ProfileWrite)?;
+        let naive_date_time = Utc::now().naive_utc();
+
+        let user: Option<user::Model> = User::find_by_id(auth_user.id).one(&my_ctx.db).await?;
+
+        let user = match user {
+            Some(user) => user,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        let mut newuser: user::ActiveModel = user.into();
+
+        if let Some(school) = school {
+            newuser.school = Set(school);
+        }
+
+        if let Some(name) = name {
+            newuser.name = Set(name);
+        }
+
+        if let Some(last_name) = last_name {
+            newuser.last_name = Set(last_name);
+        }
+
+        if let Some(class) = class {
+            newuser.class = Set(class);
+        }
+
+        if let Some(avatar_url) = avatar_url {
+            newuser.avatar_url = Set(Some(avatar_url));
+        }
+
+        newuser.updated_at = Set(naive_date_time);
+
+        newuser.clone().update(&my_ctx.db).await?;
+
+        let updated_user: user::Model = newuser.try_into_model().unwrap();
+
+        Ok(updated_user)
+    }
+
+    /// Adds a task to `room_id`. Drafts, and tasks with a future `publish_at`, are only
+    /// shown to the room's staff, and `roomEvents` only announces tasks that are visible
+    /// when they are created or edited.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::TasksWrite)")]
+    async fn create_task(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        title: String,
+        content: String,
+        #[graphql(default_with = "TaskStatus::Published")] status: TaskStatus,
+        publish_at: Option<NaiveDateTime>,
+        due_at: Option<NaiveDateTime>,
+    ) -> Result<task::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::TasksWrite)?;
+        let room = membership::require_role(my_ctx, auth_user, room_id, membership::STAFF).await?;
+        rooms::writable(&room)?;
+
+        let naive_date_time = Utc::now().naive_utc();
+        let task = task::ActiveModel {
+            created_at: Set(naive_date_time),
+            updated_at: Set(naive_date_time),
+            room_id: Set(room_id),
+            title: Set(title),
+            content: Set(content),
+            status: Set(status),
+            publish_at: Set(publish_at),
+            due_at: Set(due_at),
+            ..Default::default()
+        };
+        let task: task::Model = task.insert(&my_ctx.db).await?;
+
+        if tasks::is_visible(&task) {
+            my_ctx.events.publish(Event::TaskCreated {
+                room_id,
+                task_id: task.id,
+            });
+        }
+        Ok(task)
+    }
+
+    #[graphql(guard = "RoleGuard::new(Role::Admin).scope(Scope::AchievementsWrite)")]
+    async fn create_achievment(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        title: String,
+        description: String,
+    ) -> Result<achievment::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let naive_date_time = Utc::now().naive_utc();
+        let achievment = achievment::ActiveModel {
+            created_at: Set(naive_date_time),
+            updated_at: Set(naive_date_time),
+            title: Set(title),
+            description: Set(description),
+            ..Default::default()
+        };
+        let achievment: achievment::Model = achievment.insert(&my_ctx.db).await?;
+        Ok(achievment)
+    }
+
+    async fn add_achievement(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        user_id: i32,
+        achievment_id: i32,
+    ) -> Result<user_achievment::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let user_achievement = user_achievment::ActiveModel {
+            user_id: Set(user_id),
+            achievment_id: Set(achievment_id),
+            string: Set(format!("{}-{}", user_id, achievment_id)),
+            ..Default::default()
+        };
+
+        let user: Option<user::Model> = User::find_by_id(user_id).one(&my_ctx.db).await?;
+
+        let user = match user {
+            Some(user) => user,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        let mut newuser: user::ActiveModel = user.into();
+        let naive_date_time = Utc::now().naive_utc();
+
+        newuser.score = Set(newuser.score.unwrap() + 1);
+        newuser.updated_at = Set(naive_date_time);
+
+        newuser.update(&my_ctx.db).await?;
+
+        let achievment: user_achievment::Model = user_achievement.insert(&my_ctx.db).await?;
+
+        Ok(achievment)
+    }
+}
@@ -0,0 +1,221 @@
+use async_graphql::Object;
+use entity::{
+    room::{self, Entity as Room},
+    user::{self, Entity as User, Role},
+    user_room::{self, Entity as UserRoom, MemberRole},
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, ModelTrait, QueryFilter,
+    Set,
+};
+
+use crate::{
+    auth::{current_user, scoped_user, AuthUser},
+    personal_token::Scope,
+    Context,
+};
+
+/// Members who can create tasks and manage students.
+pub const STAFF: &[MemberRole] = &[MemberRole::Owner, MemberRole::CoTeacher];
+pub const MEMBERS: &[MemberRole] = &[
+    MemberRole::Owner,
+    MemberRole::CoTeacher,
+    MemberRole::Assistant,
+    MemberRole::Student,
+];
+
+/// Puts `user_id` into `room_id` as `role`.
+pub async fn add_member<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    room_id: i32,
+    role: MemberRole,
+) -> Result<user_room::Model, DbErr> {
+    user_room::ActiveModel {
+        user_id: Set(user_id),
+        room_id: Set(room_id),
+        string: Set(format!("{}-{}", user_id, room_id)),
+        role: Set(role),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+pub async fn find_member<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    room_id: i32,
+) -> Result<Option<user_room::Model>, DbErr> {
+    UserRoom::find()
+        .filter(user_room::Column::UserId.eq(user_id))
+        .filter(user_room::Column::RoomId.eq(room_id))
+        .one(db)
+        .await
+}
+
+/// Loads `room_id` if `user` is a member with one of `roles`. Admins may act in any room.
+pub async fn require_role(
+    my_ctx: &Context,
+    user: &AuthUser,
+    room_id: i32,
+    roles: &[MemberRole],
+) -> Result<room::Model, async_graphql::Error> {
+    let room: Option<room::Model> = Room::find_by_id(room_id).one(&my_ctx.db).await?;
+
+    let room = match room {
+        Some(room) => room,
+        None => return Err(async_graphql::Error::new("room not found".to_string())),
+    };
+
+    if user.role == Role::Admin {
+        return Ok(room);
+    }
+
+    match find_member(&my_ctx.db, user.id, room_id).await? {
+        Some(member) if roles.contains(&member.role) => Ok(room),
+        _ => Err(async_graphql::Error::new(
+            "you do not have permission to do this".to_string(),
+        )),
+    }
+}
+
+/// Whether `user` is on the staff of `room_id`. Admins count as staff everywhere.
+pub async fn is_staff(my_ctx: &Context, user: &AuthUser, room_id: i32) -> Result<bool, DbErr> {
+    if user.role == Role::Admin {
+        return Ok(true);
+    }
+    let member = find_member(&my_ctx.db, user.id, room_id).await?;
+    Ok(member.is_some_and(|member| STAFF.contains(&member.role)))
+}
+
+/// Loads the membership of `user_id` in `room_id`, failing if there is none.
+async fn member(
+    my_ctx: &Context,
+    user_id: i32,
+    room_id: i32,
+) -> Result<user_room::Model, async_graphql::Error> {
+    match find_member(&my_ctx.db, user_id, room_id).await? {
+        Some(member) => Ok(member),
+        None => Err(async_graphql::Error::new(
+            "this user is not in the room".to_string(),
+        )),
+    }
+}
+
+#[derive(Default)]
+pub struct MembershipQuery;
+
+#[Object]
+impl MembershipQuery {
+    /// Everyone in `room_id` together with their role there.
+    async fn room_members(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<Vec<user_room::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+        let room = require_role(my_ctx, auth_user, room_id, MEMBERS).await?;
+
+        let members: Vec<user_room::Model> = room.find_related(UserRoom).all(&my_ctx.db).await?;
+        Ok(members)
+    }
+}
+
+#[derive(Default)]
+pub struct MembershipMutation;
+
+#[Object]
+impl MembershipMutation {
+    /// Takes the current user out of `room_id`. Owners have to hand the room over first.
+    async fn leave_room(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let membership = member(my_ctx, auth_user.id, room_id).await?;
+        if membership.role == MemberRole::Owner {
+            return Err(async_graphql::Error::new(
+                "the owner cannot leave the room".to_string(),
+            ));
+        }
+
+        membership.delete(&my_ctx.db).await?;
+        Ok(true)
+    }
+
+    /// Removes `user_id` from `room_id`. Co-teachers may only remove assistants and students.
+    async fn remove_member(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        user_id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let room = require_role(my_ctx, auth_user, room_id, STAFF).await?;
+
+        let membership = member(my_ctx, user_id, room_id).await?;
+        let allowed = match membership.role {
+            MemberRole::Owner => false,
+            MemberRole::CoTeacher => room.owner == auth_user.id || auth_user.role == Role::Admin,
+            MemberRole::Assistant | MemberRole::Student => true,
+        };
+        if !allowed {
+            return Err(async_graphql::Error::new(
+                "you do not have permission to do this".to_string(),
+            ));
+        }
+
+        membership.delete(&my_ctx.db).await?;
+        Ok(true)
+    }
+
+    /// Changes the role of `user_id` in `room_id`. Only the owner can do this, and
+    /// ownership itself is not handed over this way.
+    async fn set_member_role(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        user_id: i32,
+        role: MemberRole,
+    ) -> Result<user_room::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        require_role(my_ctx, auth_user, room_id, &[MemberRole::Owner]).await?;
+
+        if role == MemberRole::Owner {
+            return Err(async_graphql::Error::new(
+                "ownership cannot be handed over this way".to_string(),
+            ));
+        }
+
+        let membership = member(my_ctx, user_id, room_id).await?;
+        if membership.role == MemberRole::Owner {
+            return Err(async_graphql::Error::new(
+                "the owner's role cannot be changed".to_string(),
+            ));
+        }
+
+        if role == MemberRole::CoTeacher {
+            let user: Option<user::Model> = User::find_by_id(user_id).one(&my_ctx.db).await?;
+            match user {
+                Some(user) if user.role >= Role::Teacher => {}
+                _ => {
+                    return Err(async_graphql::Error::new(
+                        "only teachers can be co-teachers".to_string(),
+                    ))
+                }
+            }
+        }
+
+        let mut membership: user_room::ActiveModel = membership.into();
+        membership.role = Set(role);
+        let membership: user_room::Model = membership.update(&my_ctx.db).await?;
+        Ok(membership)
+    }
+}
@@ -0,0 +1,301 @@
+use async_graphql::Object;
+use chrono::Utc;
+use entity::{
+    room::{self, Entity as Room},
+    room_ownership_transfer::{self, Entity as RoomOwnershipTransfer, TransferStatus},
+    user::{self, Entity as User, Role},
+    user_room::{self, Entity as UserRoom, MemberRole},
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+
+use crate::{
+    auth::{current_user, scoped_user, RoleGuard},
+    events::{Event, Notification, NotificationKind},
+    membership::{add_member, find_member, require_role},
+    personal_token::Scope,
+    Context,
+};
+
+/// Loads `user_id`, failing unless they are allowed to own rooms.
+async fn teacher(my_ctx: &Context, user_id: i32) -> Result<user::Model, async_graphql::Error> {
+    let user: Option<user::Model> = User::find_by_id(user_id).one(&my_ctx.db).await?;
+
+    match user {
+        Some(user) if user.role >= Role::Teacher => Ok(user),
+        Some(_) => Err(async_graphql::Error::new(
+            "only teachers can own rooms".to_string(),
+        )),
+        None => Err(async_graphql::Error::new("user not found".to_string())),
+    }
+}
+
+/// Cancels the open transfer offers for `room_ids`.
+async fn cancel_pending<C: ConnectionTrait>(db: &C, room_ids: Vec<i32>) -> Result<u64, DbErr> {
+    let cancelled = RoomOwnershipTransfer::update_many()
+        .col_expr(
+            room_ownership_transfer::Column::Status,
+            Expr::value(TransferStatus::Cancelled),
+        )
+        .col_expr(
+            room_ownership_transfer::Column::RespondedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(room_ownership_transfer::Column::RoomId.is_in(room_ids))
+        .filter(room_ownership_transfer::Column::Status.eq(TransferStatus::Pending))
+        .exec(db)
+        .await?;
+    Ok(cancelled.rows_affected)
+}
+
+/// Makes `to` the owner of `room_id` in both `room.owner` and `user_room`. The previous
+/// owner stays in the room as `previous`, or leaves it if that is `None`.
+async fn hand_over<C: ConnectionTrait>(
+    db: &C,
+    room_id: i32,
+    from: i32,
+    to: i32,
+    previous: Option<MemberRole>,
+) -> Result<(), DbErr> {
+    Room::update_many()
+        .col_expr(room::Column::Owner, Expr::value(to))
+        .col_expr(room::Column::UpdatedAt, Expr::value(Utc::now().naive_utc()))
+        .filter(room::Column::Id.eq(room_id))
+        .exec(db)
+        .await?;
+
+    match previous {
+        Some(role) => {
+            UserRoom::update_many()
+                .col_expr(user_room::Column::Role, Expr::value(role))
+                .filter(user_room::Column::UserId.eq(from))
+                .filter(user_room::Column::RoomId.eq(room_id))
+                .exec(db)
+                .await?;
+        }
+        None => {
+            UserRoom::delete_many()
+                .filter(user_room::Column::UserId.eq(from))
+                .filter(user_room::Column::RoomId.eq(room_id))
+                .exec(db)
+                .await?;
+        }
+    }
+
+    match find_member(db, to, room_id).await? {
+        Some(membership) => {
+            let mut membership: user_room::ActiveModel = membership.into();
+            membership.role = Set(MemberRole::Owner);
+            membership.update(db).await?;
+        }
+        None => {
+            add_member(db, to, room_id, MemberRole::Owner).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves a pending offer made to the current user to `status`.
+async fn respond(
+    ctx: &async_graphql::Context<'_>,
+    transfer_id: i32,
+    status: TransferStatus,
+) -> Result<room_ownership_transfer::Model, async_graphql::Error> {
+    let my_ctx = ctx.data::<Context>().unwrap();
+    let auth_user = current_user(ctx)?;
+
+    let transfer: Option<room_ownership_transfer::Model> = RoomOwnershipTransfer::find()
+        .filter(room_ownership_transfer::Column::Id.eq(transfer_id))
+        .filter(room_ownership_transfer::Column::ToUserId.eq(auth_user.id))
+        .one(&my_ctx.db)
+        .await?;
+
+    let transfer = match transfer {
+        Some(transfer) => transfer,
+        None => {
+            return Err(async_graphql::Error::new(
+                "there is no pending transfer with this id".to_string(),
+            ))
+        }
+    };
+
+    if status == TransferStatus::Accepted {
+        teacher(my_ctx, auth_user.id).await?;
+    }
+
+    let now = Utc::now().naive_utc();
+    let txn = my_ctx.db.begin().await?;
+
+    let responded = RoomOwnershipTransfer::update_many()
+        .col_expr(room_ownership_transfer::Column::Status, Expr::value(status))
+        .col_expr(
+            room_ownership_transfer::Column::RespondedAt,
+            Expr::value(now),
+        )
+        .filter(room_ownership_transfer::Column::Id.eq(transfer.id))
+        .filter(room_ownership_transfer::Column::Status.eq(TransferStatus::Pending))
+        .exec(&txn)
+        .await?;
+
+    if responded.rows_affected == 0 {
+        return Err(async_graphql::Error::new(
+            "there is no pending transfer with this id".to_string(),
+        ));
+    }
+
+    if status == TransferStatus::Accepted {
+        hand_over(
+            &txn,
+            transfer.room_id,
+            transfer.from_user_id,
+            transfer.to_user_id,
+            Some(MemberRole::CoTeacher),
+        )
+        .await?;
+    }
+
+    txn.commit().await?;
+
+    Ok(room_ownership_transfer::Model {
+        status,
+        responded_at: Some(now),
+        ..transfer
+    })
+}
+
+#[derive(Default)]
+pub struct OwnershipQuery;
+
+#[Object]
+impl OwnershipQuery {
+    /// Rooms other teachers are offering to hand over to the current user.
+    async fn room_ownership_offers(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<Vec<room_ownership_transfer::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let transfers: Vec<room_ownership_transfer::Model> = RoomOwnershipTransfer::find()
+            .filter(room_ownership_transfer::Column::ToUserId.eq(auth_user.id))
+            .filter(room_ownership_transfer::Column::Status.eq(TransferStatus::Pending))
+            .order_by_asc(room_ownership_transfer::Column::CreatedAt)
+            .all(&my_ctx.db)
+            .await?;
+        Ok(transfers)
+    }
+}
+
+#[derive(Default)]
+pub struct OwnershipMutation;
+
+#[Object]
+impl OwnershipMutation {
+    /// Offers `room_id` to the teacher `new_owner_id`. It changes hands once they accept;
+    /// until then, a new offer replaces this one.
+    async fn transfer_room_ownership(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        new_owner_id: i32,
+    ) -> Result<room_ownership_transfer::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let room = require_role(my_ctx, auth_user, room_id, &[MemberRole::Owner]).await?;
+
+        if room.owner == new_owner_id {
+            return Err(async_graphql::Error::new(
+                "this teacher already owns the room".to_string(),
+            ));
+        }
+        teacher(my_ctx, new_owner_id).await?;
+
+        let txn = my_ctx.db.begin().await?;
+        cancel_pending(&txn, vec![room_id]).await?;
+
+        let transfer = room_ownership_transfer::ActiveModel {
+            room_id: Set(room_id),
+            from_user_id: Set(room.owner),
+            to_user_id: Set(new_owner_id),
+            status: Set(TransferStatus::Pending),
+            created_at: Set(Utc::now().naive_utc()),
+            responded_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        txn.commit().await?;
+
+        my_ctx.events.publish(Event::Notification {
+            user_id: new_owner_id,
+            notification: Notification::new(
+                NotificationKind::RoomOwnershipOffered,
+                Some(room_id),
+                format!("You were offered ownership of {}", room.name),
+            ),
+        });
+        Ok(transfer)
+    }
+
+    /// Takes over the room. The previous owner stays on as a co-teacher.
+    async fn accept_room_ownership(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        transfer_id: i32,
+    ) -> Result<room_ownership_transfer::Model, async_graphql::Error> {
+        respond(ctx, transfer_id, TransferStatus::Accepted).await
+    }
+
+    /// Turns the offer down; the room stays with its owner.
+    async fn decline_room_ownership(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        transfer_id: i32,
+    ) -> Result<room_ownership_transfer::Model, async_graphql::Error> {
+        respond(ctx, transfer_id, TransferStatus::Declined).await
+    }
+
+    /// Hands every room of `from_user_id` to `to_user_id` at once, e.g. when a teacher
+    /// leaves the school. The previous owner is taken out of those rooms.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn reassign_rooms(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        from_user_id: i32,
+        to_user_id: i32,
+    ) -> Result<Vec<room::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        if from_user_id == to_user_id {
+            return Err(async_graphql::Error::new(
+                "rooms cannot be reassigned to the same user".to_string(),
+            ));
+        }
+        teacher(my_ctx, to_user_id).await?;
+
+        let txn = my_ctx.db.begin().await?;
+
+        let rooms: Vec<room::Model> = Room::find()
+            .filter(room::Column::Owner.eq(from_user_id))
+            .all(&txn)
+            .await?;
+        let ids: Vec<i32> = rooms.iter().map(|room| room.id).collect();
+
+        cancel_pending(&txn, ids.clone()).await?;
+        for room_id in &ids {
+            hand_over(&txn, *room_id, from_user_id, to_user_id, None).await?;
+        }
+
+        let rooms: Vec<room::Model> = Room::find()
+            .filter(room::Column::Id.is_in(ids))
+            .all(&txn)
+            .await?;
+
+        txn.commit().await?;
+        Ok(rooms)
+    }
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashSet, fs, sync::Arc};
+
+/// Passwords nobody may use, unless `PASSWORD_BLOCKLIST` points somewhere else.
+const DEFAULT_BLOCKLIST: &str = include_str!("../data/common-passwords.txt");
+const DEFAULT_MIN_LENGTH: usize = 8;
+
+/// Rules a new password has to pass on `register`, `resetPassword` and `changePassword`.
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    blocklist: Arc<HashSet<String>>,
+}
+
+impl PasswordPolicy {
+    pub fn from_env() -> Self {
+        let min_length = match dotenvy::var("PASSWORD_MIN_LENGTH") {
+            Ok(value) => value.parse().expect("PASSWORD_MIN_LENGTH must be a number"),
+            Err(_) => DEFAULT_MIN_LENGTH,
+        };
+
+        let blocklist = match dotenvy::var("PASSWORD_BLOCKLIST") {
+            Ok(path) => fs::read_to_string(&path)
+                .unwrap_or_else(|err| panic!("could not read {}: {}", path, err)),
+            Err(_) => DEFAULT_BLOCKLIST.to_string(),
+        };
+
+        Self {
+            min_length,
+            blocklist: Arc::new(parse_blocklist(&blocklist)),
+        }
+    }
+
+    /// Fails with a message for the user if `password` breaks a rule.
+    pub fn check(
+        &self,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> Result<(), async_graphql::Error> {
+        if password.chars().count() < self.min_length {
+            return Err(async_graphql::Error::new(format!(
+                "the password must be at least {} characters long",
+                self.min_length
+            )));
+        }
+
+        let lowered = password.to_lowercase();
+        if lowered == username.to_lowercase() || lowered == email.to_lowercase() {
+            return Err(async_graphql::Error::new(
+                "the password must not be your username or email".to_string(),
+            ));
+        }
+
+        if self.blocklist.contains(&lowered) {
+            return Err(async_graphql::Error::new(
+                "this password is too common".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_blocklist(list: &str) -> HashSet<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            blocklist: Arc::new(parse_blocklist("# common\nPassword1\n\n  qwertyuiop \n")),
+        }
+    }
+
+    #[test]
+    fn accepts_a_good_password() {
+        assert!(policy()
+            .check("correct horse battery", "alice", "alice@example.com")
+            .is_ok());
+    }
+
+    #[test]
+    fn rejects_short_passwords() {
+        assert!(policy()
+            .check("short", "alice", "alice@example.com")
+            .is_err());
+        // Counted in characters, not bytes.
+        assert!(policy()
+            .check("ąčęėįšųū", "alice", "alice@example.com")
+            .is_ok());
+        assert!(policy()
+            .check("ąčęėįšų", "alice", "alice@example.com")
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_username_and_email() {
+        assert!(policy()
+            .check("AliceWonder", "alicewonder", "alice@example.com")
+            .is_err());
+        assert!(policy()
+            .check("Alice@Example.com", "alice", "alice@example.com")
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_blocklisted_passwords() {
+        assert!(policy()
+            .check("PASSWORD1", "alice", "alice@example.com")
+            .is_err());
+        assert!(policy()
+            .check("qwertyuiop", "alice", "alice@example.com")
+            .is_err());
+        assert!(policy()
+            .check("# common", "alice", "alice@example.com")
+            .is_ok());
+    }
+}
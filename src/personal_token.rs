@@ -0,0 +1,249 @@
+use async_graphql::{Enum, Object, SimpleObject};
+use chrono::{Duration, NaiveDateTime, Utc};
+use entity::{
+    personal_access_token::{self, Entity as PersonalAccessToken},
+    user::Entity as User,
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr,
+    EntityTrait, QueryFilter, QueryOrder, Set,
+};
+
+use crate::{
+    auth::{current_user, hash_token, random_token, AuthUser},
+    Context,
+};
+
+/// Personal access tokens start with this, so they can be told apart from JWTs.
+pub const TOKEN_PREFIX: &str = "pat_";
+/// Tokens that should live longer than ten years can be created without an expiry.
+const MAX_EXPIRES_IN_DAYS: i64 = 3650;
+
+/// What a personal access token may do. Session tokens may do everything their role allows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Enum)]
+pub enum Scope {
+    ProfileRead,
+    ProfileWrite,
+    RoomsRead,
+    RoomsWrite,
+    TasksRead,
+    TasksWrite,
+    AchievementsWrite,
+}
+
+impl Scope {
+    const ALL: [Scope; 7] = [
+        Scope::ProfileRead,
+        Scope::ProfileWrite,
+        Scope::RoomsRead,
+        Scope::RoomsWrite,
+        Scope::TasksRead,
+        Scope::TasksWrite,
+        Scope::AchievementsWrite,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::ProfileRead => "profile:read",
+            Scope::ProfileWrite => "profile:write",
+            Scope::RoomsRead => "rooms:read",
+            Scope::RoomsWrite => "rooms:write",
+            Scope::TasksRead => "tasks:read",
+            Scope::TasksWrite => "tasks:write",
+            Scope::AchievementsWrite => "achievements:write",
+        }
+    }
+
+    fn parse(scope: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|known| known.as_str() == scope)
+    }
+}
+
+fn parse_scopes(scopes: &str) -> Vec<Scope> {
+    scopes.split_whitespace().filter_map(Scope::parse).collect()
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "PersonalAccessToken")]
+pub struct TokenInfo {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl From<personal_access_token::Model> for TokenInfo {
+    fn from(model: personal_access_token::Model) -> Self {
+        Self {
+            id: model.id,
+            scopes: parse_scopes(&model.scopes),
+            name: model.name,
+            created_at: model.created_at,
+            last_used_at: model.last_used_at,
+            expires_at: model.expires_at,
+            revoked_at: model.revoked_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct CreatedToken {
+    /// The token itself. It cannot be shown again.
+    pub token: String,
+    pub info: TokenInfo,
+}
+
+/// Resolves a `pat_` token to its user, or `None` if it is unknown, revoked or expired.
+pub async fn authenticate(
+    db: &DatabaseConnection,
+    secret: &str,
+    token: &str,
+) -> Result<Option<AuthUser>, DbErr> {
+    let now = Utc::now().naive_utc();
+
+    let found = PersonalAccessToken::find()
+        .filter(personal_access_token::Column::TokenHash.eq(hash_token(secret, token)))
+        .filter(personal_access_token::Column::RevokedAt.is_null())
+        .filter(
+            Condition::any()
+                .add(personal_access_token::Column::ExpiresAt.is_null())
+                .add(personal_access_token::Column::ExpiresAt.gt(now)),
+        )
+        .find_also_related(User)
+        .one(db)
+        .await?;
+
+    let (found, user) = match found {
+        Some((found, Some(user))) => (found, user),
+        _ => return Ok(None),
+    };
+
+    PersonalAccessToken::update_many()
+        .col_expr(personal_access_token::Column::LastUsedAt, Expr::value(now))
+        .filter(personal_access_token::Column::Id.eq(found.id))
+        .exec(db)
+        .await?;
+
+    Ok(Some(AuthUser {
+        id: user.id,
+        role: user.role,
+        email_verified: user.email_verified_at.is_some(),
+        password_change_required: user.password_change_required,
+        scopes: Some(parse_scopes(&found.scopes)),
+        expires_at: found.expires_at,
+    }))
+}
+
+#[derive(Default)]
+pub struct PersonalTokenQuery;
+
+#[Object]
+impl PersonalTokenQuery {
+    /// The current user's personal access tokens, newest first.
+    async fn personal_access_tokens(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<Vec<TokenInfo>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let tokens: Vec<personal_access_token::Model> = PersonalAccessToken::find()
+            .filter(personal_access_token::Column::UserId.eq(auth_user.id))
+            .order_by_desc(personal_access_token::Column::CreatedAt)
+            .all(&my_ctx.db)
+            .await?;
+
+        Ok(tokens.into_iter().map(TokenInfo::from).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct PersonalTokenMutation;
+
+#[Object]
+impl PersonalTokenMutation {
+    /// Creates a token that acts as the current user, limited to `scopes`. It never
+    /// expires unless `expires_in_days` is given.
+    async fn create_personal_access_token(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        name: String,
+        scopes: Vec<Scope>,
+        expires_in_days: Option<i64>,
+    ) -> Result<CreatedToken, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        if scopes.is_empty() {
+            return Err(async_graphql::Error::new(
+                "a token needs at least one scope".to_string(),
+            ));
+        }
+
+        let expires_at = match expires_in_days {
+            Some(days) if !(1..=MAX_EXPIRES_IN_DAYS).contains(&days) => {
+                return Err(async_graphql::Error::new(format!(
+                    "expiresInDays must be between 1 and {}",
+                    MAX_EXPIRES_IN_DAYS
+                )))
+            }
+            Some(days) => Some(Utc::now().naive_utc() + Duration::days(days)),
+            None => None,
+        };
+
+        let mut names: Vec<&str> = scopes.iter().map(|scope| scope.as_str()).collect();
+        names.sort();
+        names.dedup();
+
+        let token = format!("{}{}", TOKEN_PREFIX, random_token());
+
+        let model: personal_access_token::Model = personal_access_token::ActiveModel {
+            user_id: Set(auth_user.id),
+            name: Set(name),
+            token_hash: Set(hash_token(&my_ctx.refr_key, &token)),
+            scopes: Set(names.join(" ")),
+            created_at: Set(Utc::now().naive_utc()),
+            last_used_at: Set(None),
+            expires_at: Set(expires_at),
+            revoked_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&my_ctx.db)
+        .await?;
+
+        Ok(CreatedToken {
+            token,
+            info: model.into(),
+        })
+    }
+
+    /// Revokes one of the current user's personal access tokens.
+    async fn revoke_personal_access_token(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let revoked = PersonalAccessToken::update_many()
+            .col_expr(
+                personal_access_token::Column::RevokedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .filter(personal_access_token::Column::Id.eq(id))
+            .filter(personal_access_token::Column::UserId.eq(auth_user.id))
+            .filter(personal_access_token::Column::RevokedAt.is_null())
+            .exec(&my_ctx.db)
+            .await?;
+
+        if revoked.rows_affected == 0 {
+            return Err(async_graphql::Error::new("token not found".to_string()));
+        }
+
+        Ok(true)
+    }
+}
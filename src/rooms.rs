@@ -0,0 +1,413 @@
+use std::collections::{HashMap, HashSet};
+
+use async_graphql::{
+    connection::{Connection, Edge},
+    Enum, InputObject, Object,
+};
+use chrono::Utc;
+use entity::{
+    room::{self, Entity as Room},
+    task::{self, Entity as Task},
+    user::{self, Role},
+    user_room::{self, Entity as UserRoom, MemberRole},
+};
+use sea_orm::{
+    sea_query::{Expr, Query},
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, JoinType, ModelTrait, Order,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait, Select, Set, Value,
+};
+
+use crate::{
+    announcements,
+    auth::{scoped_user, AuthUser, RoleGuard},
+    membership::{require_role, STAFF},
+    personal_token::Scope,
+    tasks, Context,
+};
+
+/// Fails if `room` is archived, since archived rooms are read-only.
+pub fn writable(room: &room::Model) -> Result<(), async_graphql::Error> {
+    if room.archived_at.is_some() {
+        return Err(async_graphql::Error::new(
+            "this room is archived".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+const DEFAULT_PAGE_SIZE: i32 = 20;
+const MAX_PAGE_SIZE: i32 = 100;
+
+#[derive(InputObject, Default)]
+pub struct RoomFilter {
+    /// `true` lists only archived rooms. Archived rooms are hidden otherwise.
+    pub archived: Option<bool>,
+    pub subject: Option<String>,
+    /// The school of the room's owner.
+    pub school: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Enum)]
+pub enum RoomSort {
+    #[default]
+    Newest,
+    Oldest,
+    Name,
+    RecentlyUpdated,
+}
+
+impl RoomSort {
+    fn column(self) -> room::Column {
+        match self {
+            RoomSort::Newest | RoomSort::Oldest => room::Column::CreatedAt,
+            RoomSort::Name => room::Column::Name,
+            RoomSort::RecentlyUpdated => room::Column::UpdatedAt,
+        }
+    }
+
+    fn order(self) -> Order {
+        match self {
+            RoomSort::Oldest | RoomSort::Name => Order::Asc,
+            RoomSort::Newest | RoomSort::RecentlyUpdated => Order::Desc,
+        }
+    }
+
+    /// Rooms that come after `cursor` in this order. Ties are broken by id.
+    fn after(self, cursor: &room::Model) -> Condition {
+        let value: Value = match self {
+            RoomSort::Newest | RoomSort::Oldest => cursor.created_at.into(),
+            RoomSort::Name => cursor.name.clone().into(),
+            RoomSort::RecentlyUpdated => cursor.updated_at.into(),
+        };
+        let column = self.column();
+
+        let (past_value, past_id) = match self.order() {
+            Order::Desc => (column.lt(value.clone()), room::Column::Id.lt(cursor.id)),
+            _ => (column.gt(value.clone()), room::Column::Id.gt(cursor.id)),
+        };
+
+        Condition::any()
+            .add(past_value)
+            .add(Condition::all().add(column.eq(value)).add(past_id))
+    }
+}
+
+/// Counts rows of `select` per room for `ids`.
+async fn count_per_room<E: EntityTrait>(
+    my_ctx: &Context,
+    select: Select<E>,
+    room_id: E::Column,
+    ids: &[i32],
+) -> Result<HashMap<i32, i64>, async_graphql::Error> {
+    let counts: Vec<(i32, i64)> = select
+        .select_only()
+        .column(room_id)
+        .column_as(Expr::col(room_id).count(), "count")
+        .filter(room_id.is_in(ids.to_vec()))
+        .group_by(room_id)
+        .into_tuple()
+        .all(&my_ctx.db)
+        .await?;
+    Ok(counts.into_iter().collect())
+}
+
+/// The rooms among `ids` where `user_id` is staff.
+async fn staff_rooms(
+    my_ctx: &Context,
+    user_id: i32,
+    ids: &[i32],
+) -> Result<HashSet<i32>, async_graphql::Error> {
+    let rooms: Vec<i32> = UserRoom::find()
+        .select_only()
+        .column(user_room::Column::RoomId)
+        .filter(user_room::Column::UserId.eq(user_id))
+        .filter(user_room::Column::RoomId.is_in(ids.to_vec()))
+        .filter(user_room::Column::Role.is_in(STAFF.to_vec()))
+        .into_tuple()
+        .all(&my_ctx.db)
+        .await?;
+    Ok(rooms.into_iter().collect())
+}
+
+/// Runs one page of a room listing on top of `select`. Unread announcements are
+/// counted for `viewer`, if given, and rooms they are not staff of only count the tasks
+/// students can see.
+async fn list_rooms(
+    my_ctx: &Context,
+    viewer: Option<&AuthUser>,
+    mut select: Select<Room>,
+    filter: RoomFilter,
+    sort: RoomSort,
+    first: Option<i32>,
+    after: Option<String>,
+) -> Result<Connection<i32, room::Model>, async_graphql::Error> {
+    let first = first.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&first) {
+        return Err(async_graphql::Error::new(format!(
+            "first must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+
+    select = match filter.archived {
+        Some(true) => select.filter(room::Column::ArchivedAt.is_not_null()),
+        _ => select.filter(room::Column::ArchivedAt.is_null()),
+    };
+    if let Some(subject) = filter.subject {
+        select = select.filter(room::Column::Subject.eq(subject));
+    }
+    if let Some(school) = filter.school {
+        select = select
+            .join(JoinType::InnerJoin, room::Relation::User.def())
+            .filter(user::Column::School.eq(school));
+    }
+
+    if let Some(after) = &after {
+        let cursor: Option<room::Model> = match after.parse::<i32>() {
+            Ok(id) => Room::find_by_id(id).one(&my_ctx.db).await?,
+            Err(_) => None,
+        };
+        let cursor = match cursor {
+            Some(cursor) => cursor,
+            None => return Err(async_graphql::Error::new("invalid cursor".to_string())),
+        };
+        select = select.filter(sort.after(&cursor));
+    }
+
+    let mut rooms: Vec<room::Model> = select
+        .order_by(sort.column(), sort.order())
+        .order_by(room::Column::Id, sort.order())
+        .limit(first as u64 + 1)
+        .all(&my_ctx.db)
+        .await?;
+
+    let has_next_page = rooms.len() > first as usize;
+    rooms.truncate(first as usize);
+
+    let ids: Vec<i32> = rooms.iter().map(|room| room.id).collect();
+    let members = count_per_room(my_ctx, UserRoom::find(), user_room::Column::RoomId, &ids).await?;
+    let all_tasks = count_per_room(my_ctx, Task::find(), task::Column::RoomId, &ids).await?;
+    let visible_tasks = match viewer {
+        Some(user) if user.role != Role::Admin => {
+            let visible = Task::find().filter(tasks::visible());
+            Some((
+                staff_rooms(my_ctx, user.id, &ids).await?,
+                count_per_room(my_ctx, visible, task::Column::RoomId, &ids).await?,
+            ))
+        }
+        _ => None,
+    };
+    let unread = match viewer {
+        Some(user) => Some(announcements::unread_counts(&my_ctx.db, user.id, &ids).await?),
+        None => None,
+    };
+
+    let mut connection = Connection::new(after.is_some(), has_next_page);
+    connection.edges.extend(rooms.into_iter().map(|mut room| {
+        room.member_count = Some(members.get(&room.id).copied().unwrap_or(0));
+        let tasks = match &visible_tasks {
+            Some((staff, visible)) if !staff.contains(&room.id) => visible,
+            _ => &all_tasks,
+        };
+        room.task_count = Some(tasks.get(&room.id).copied().unwrap_or(0));
+        room.unread_announcements = unread
+            .as_ref()
+            .map(|unread| unread.get(&room.id).copied().unwrap_or(0));
+        Edge::new(room.id, room)
+    }));
+    Ok(connection)
+}
+
+/// Empty strings clear an optional text field.
+fn optional_text(value: String) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+/// Sets or clears `archived_at` of `room_id`, failing if it already was in that state.
+async fn set_archived(
+    ctx: &async_graphql::Context<'_>,
+    room_id: i32,
+    archived: bool,
+) -> Result<room::Model, async_graphql::Error> {
+    let my_ctx = ctx.data::<Context>().unwrap();
+    let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+    require_role(my_ctx, auth_user, room_id, &[MemberRole::Owner]).await?;
+
+    let now = Utc::now().naive_utc();
+    let archived_at = if archived { Some(now) } else { None };
+    let state = if archived {
+        room::Column::ArchivedAt.is_null()
+    } else {
+        room::Column::ArchivedAt.is_not_null()
+    };
+
+    let changed = Room::update_many()
+        .col_expr(room::Column::ArchivedAt, Expr::value(archived_at))
+        .col_expr(room::Column::UpdatedAt, Expr::value(now))
+        .filter(room::Column::Id.eq(room_id))
+        .filter(state)
+        .exec(&my_ctx.db)
+        .await?;
+
+    if changed.rows_affected == 0 {
+        let message = if archived {
+            "this room is already archived"
+        } else {
+            "this room is not archived"
+        };
+        return Err(async_graphql::Error::new(message.to_string()));
+    }
+
+    let room: Option<room::Model> = Room::find_by_id(room_id).one(&my_ctx.db).await?;
+
+    match room {
+        Some(room) => Ok(room),
+        None => Err(async_graphql::Error::new("room not found".to_string())),
+    }
+}
+
+#[derive(Default)]
+pub struct RoomQuery;
+
+#[Object]
+impl RoomQuery {
+    /// Rooms the current user is a member of, in any role.
+    async fn my_rooms(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] filter: RoomFilter,
+        #[graphql(default)] sort: RoomSort,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<i32, room::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+
+        let select = Room::find().filter(
+            room::Column::Id.in_subquery(
+                Query::select()
+                    .column(user_room::Column::RoomId)
+                    .from(UserRoom)
+                    .and_where(user_room::Column::UserId.eq(auth_user.id))
+                    .to_owned(),
+            ),
+        );
+        list_rooms(my_ctx, Some(auth_user), select, filter, sort, first, after).await
+    }
+
+    /// Rooms the current user owns.
+    async fn owned_rooms(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] filter: RoomFilter,
+        #[graphql(default)] sort: RoomSort,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<i32, room::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsRead)?;
+
+        let select = Room::find().filter(room::Column::Owner.eq(auth_user.id));
+        list_rooms(my_ctx, Some(auth_user), select, filter, sort, first, after).await
+    }
+
+    /// Every room on the platform.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn rooms(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] filter: RoomFilter,
+        #[graphql(default)] sort: RoomSort,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<i32, room::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        list_rooms(my_ctx, None, Room::find(), filter, sort, first, after).await
+    }
+}
+
+#[derive(Default)]
+pub struct RoomMutation;
+
+#[Object]
+impl RoomMutation {
+    /// Changes the details of `room_id`. An empty `description` or `subject` clears it.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsWrite)")]
+    async fn update_room(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+        name: Option<String>,
+        description: Option<String>,
+        subject: Option<String>,
+    ) -> Result<room::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let room = require_role(my_ctx, auth_user, room_id, &[MemberRole::Owner]).await?;
+        writable(&room)?;
+
+        let mut room: room::ActiveModel = room.into();
+
+        if let Some(name) = name {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(async_graphql::Error::new(
+                    "the room needs a name".to_string(),
+                ));
+            }
+            room.name = Set(name.to_string());
+        }
+
+        if let Some(description) = description {
+            room.description = Set(optional_text(description));
+        }
+
+        if let Some(subject) = subject {
+            room.subject = Set(optional_text(subject));
+        }
+
+        room.updated_at = Set(Utc::now().naive_utc());
+        let room: room::Model = room.update(&my_ctx.db).await?;
+        Ok(room)
+    }
+
+    /// Makes `room_id` read-only and hides it from room lists.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsWrite)")]
+    async fn archive_room(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<room::Model, async_graphql::Error> {
+        set_archived(ctx, room_id, true).await
+    }
+
+    /// Makes an archived room writable and listed again.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsWrite)")]
+    async fn unarchive_room(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<room::Model, async_graphql::Error> {
+        set_archived(ctx, room_id, false).await
+    }
+
+    /// Deletes `room_id` together with its tasks, members and invite codes.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::RoomsWrite)")]
+    async fn delete_room(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::RoomsWrite)?;
+        let room = require_role(my_ctx, auth_user, room_id, &[MemberRole::Owner]).await?;
+
+        room.delete(&my_ctx.db).await?;
+        Ok(true)
+    }
+}
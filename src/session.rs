@@ -0,0 +1,224 @@
+use async_graphql::Object;
+use chrono::{Duration, NaiveDateTime, Utc};
+use entity::session::{self, Entity as Session};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, Set,
+};
+
+use crate::{
+    auth::{current_user, hash_token, random_token, ClientInfo},
+    Context,
+};
+
+/// Lifetime of a single refresh token, in minutes.
+const REFRESH_EXPIRATION: i64 = 180;
+
+async fn insert(
+    db: &DatabaseConnection,
+    secret: &str,
+    user_id: i32,
+    family: String,
+    created_at: NaiveDateTime,
+    client: &ClientInfo,
+) -> Result<String, DbErr> {
+    let token = random_token();
+    let now = Utc::now().naive_utc();
+
+    session::ActiveModel {
+        user_id: Set(user_id),
+        family: Set(family),
+        token_hash: Set(hash_token(secret, &token)),
+        user_agent: Set(client.user_agent.clone()),
+        ip: Set(client.ip.clone()),
+        created_at: Set(created_at),
+        issued_at: Set(now),
+        expires_at: Set(now + Duration::minutes(REFRESH_EXPIRATION)),
+        revoked_at: Set(None),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(token)
+}
+
+/// Starts a new token family for `user_id` and returns its first refresh token.
+pub async fn start(
+    db: &DatabaseConnection,
+    secret: &str,
+    user_id: i32,
+    client: &ClientInfo,
+) -> Result<String, DbErr> {
+    let now = Utc::now().naive_utc();
+    insert(db, secret, user_id, random_token(), now, client).await
+}
+
+/// Exchanges `token` for the next refresh token of its family.
+///
+/// Presenting a token that was already rotated or revoked means it has leaked,
+/// so the whole family is revoked. Returns the owner's id and the new token.
+pub async fn rotate(
+    db: &DatabaseConnection,
+    secret: &str,
+    token: &str,
+    client: &ClientInfo,
+) -> Result<(i32, String), async_graphql::Error> {
+    let current: Option<session::Model> = Session::find_by_token_hash(hash_token(secret, token))
+        .one(db)
+        .await?;
+
+    let current = match current {
+        Some(current) => current,
+        None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+    };
+
+    let now = Utc::now().naive_utc();
+
+    if current.revoked_at.is_some() {
+        revoke_family(db, &current.family).await?;
+        return Err(async_graphql::Error::new("Wrong token".to_string()));
+    }
+
+    if current.expires_at < now {
+        return Err(async_graphql::Error::new("Wrong token".to_string()));
+    }
+
+    // Only one caller may rotate a given token; a concurrent loser is treated as reuse.
+    let rotated = Session::update_many()
+        .col_expr(session::Column::RevokedAt, Expr::value(now))
+        .filter(session::Column::Id.eq(current.id))
+        .filter(session::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+
+    if rotated.rows_affected == 0 {
+        revoke_family(db, &current.family).await?;
+        return Err(async_graphql::Error::new("Wrong token".to_string()));
+    }
+
+    let token = insert(
+        db,
+        secret,
+        current.user_id,
+        current.family,
+        current.created_at,
+        client,
+    )
+    .await?;
+
+    Ok((current.user_id, token))
+}
+
+/// Revokes every token of `family` that is still live.
+pub async fn revoke_family(db: &DatabaseConnection, family: &str) -> Result<(), DbErr> {
+    Session::update_many()
+        .col_expr(
+            session::Column::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(session::Column::Family.eq(family))
+        .filter(session::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Revokes every live token of `user_id`.
+pub async fn revoke_all(db: &DatabaseConnection, user_id: i32) -> Result<(), DbErr> {
+    Session::update_many()
+        .col_expr(
+            session::Column::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(session::Column::UserId.eq(user_id))
+        .filter(session::Column::RevokedAt.is_null())
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct SessionQuery;
+
+#[Object]
+impl SessionQuery {
+    /// Sessions of the current user that can still be refreshed.
+    async fn my_sessions(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<Vec<session::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let sessions: Vec<session::Model> = Session::find()
+            .filter(session::Column::UserId.eq(auth_user.id))
+            .filter(session::Column::RevokedAt.is_null())
+            .filter(session::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+            .order_by_desc(session::Column::IssuedAt)
+            .all(&my_ctx.db)
+            .await?;
+
+        Ok(sessions)
+    }
+}
+
+#[derive(Default)]
+pub struct SessionMutation;
+
+#[Object]
+impl SessionMutation {
+    /// Ends the session `refresh_token` belongs to.
+    async fn logout(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        refresh_token: String,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let current: Option<session::Model> =
+            Session::find_by_token_hash(hash_token(&my_ctx.refr_key, &refresh_token))
+                .one(&my_ctx.db)
+                .await?;
+
+        let current = match current {
+            Some(current) => current,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        revoke_family(&my_ctx.db, &current.family).await?;
+        Ok(true)
+    }
+
+    /// Ends every session of the current user.
+    async fn logout_all_devices(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        revoke_all(&my_ctx.db, auth_user.id).await?;
+        Ok(true)
+    }
+
+    /// Ends one of the sessions listed by `mySessions`.
+    async fn revoke_session(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let session: Option<session::Model> = Session::find_by_id(id).one(&my_ctx.db).await?;
+
+        let session = match session {
+            Some(session) if session.user_id == auth_user.id => session,
+            _ => return Err(async_graphql::Error::new("session not found".to_string())),
+        };
+
+        revoke_family(&my_ctx.db, &session.family).await?;
+        Ok(true)
+    }
+}
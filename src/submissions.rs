@@ -0,0 +1,303 @@
+use async_graphql::{Object, SimpleObject};
+use chrono::{NaiveDateTime, Utc};
+use entity::{
+    submission::{self, Entity as Submission, SubmissionStatus},
+    task::{self, Entity as Task},
+    user::Role,
+    user_room::MemberRole,
+};
+use sea_orm::{
+    sea_query::Query, ActiveModelTrait, ColumnTrait, EntityTrait, ModelTrait, QueryFilter,
+    QueryOrder, Set,
+};
+
+use crate::{
+    auth::{current_user, scoped_user, RoleGuard},
+    events::{Event, Notification, NotificationKind},
+    membership::{is_staff, require_role, MEMBERS, STAFF},
+    personal_token::Scope,
+    rooms::writable,
+    tasks::is_visible,
+    Context,
+};
+
+const MAX_ATTACHMENTS: usize = 10;
+
+#[derive(SimpleObject)]
+#[graphql(name = "Submission")]
+pub struct SubmissionInfo {
+    pub id: i32,
+    pub task_id: i32,
+    pub user_id: i32,
+    pub body: String,
+    pub attachments: Vec<String>,
+    pub status: SubmissionStatus,
+    pub attempt: i32,
+    pub grade: Option<i32>,
+    pub feedback: Option<String>,
+    pub graded_by: Option<i32>,
+    pub submitted_at: NaiveDateTime,
+    pub graded_at: Option<NaiveDateTime>,
+}
+
+impl From<submission::Model> for SubmissionInfo {
+    fn from(model: submission::Model) -> Self {
+        Self {
+            id: model.id,
+            task_id: model.task_id,
+            user_id: model.user_id,
+            attachments: model.attachments.lines().map(str::to_string).collect(),
+            body: model.body,
+            status: model.status,
+            attempt: model.attempt,
+            grade: model.grade,
+            feedback: model.feedback,
+            graded_by: model.graded_by,
+            submitted_at: model.submitted_at,
+            graded_at: model.graded_at,
+        }
+    }
+}
+
+/// Checks that every attachment is a single http(s) URL and joins them for storage.
+fn join_attachments(urls: Vec<String>) -> Result<String, async_graphql::Error> {
+    if urls.len() > MAX_ATTACHMENTS {
+        return Err(async_graphql::Error::new(format!(
+            "a submission can have at most {} attachments",
+            MAX_ATTACHMENTS
+        )));
+    }
+
+    let mut checked = Vec::new();
+    for url in urls {
+        let url = url.trim();
+        let valid = (url.starts_with("https://") || url.starts_with("http://"))
+            && !url.contains(char::is_whitespace);
+        if !valid {
+            return Err(async_graphql::Error::new(format!(
+                "`{}` is not a valid attachment URL",
+                url
+            )));
+        }
+        checked.push(url.to_string());
+    }
+    Ok(checked.join("\n"))
+}
+
+async fn find_task(my_ctx: &Context, task_id: i32) -> Result<task::Model, async_graphql::Error> {
+    let task: Option<task::Model> = Task::find_by_id(task_id).one(&my_ctx.db).await?;
+
+    match task {
+        Some(task) => Ok(task),
+        None => Err(async_graphql::Error::new("task not found".to_string())),
+    }
+}
+
+#[derive(Default)]
+pub struct SubmissionQuery;
+
+#[Object]
+impl SubmissionQuery {
+    /// The current user's submissions, newest first, optionally only those in `room_id`.
+    async fn my_submissions(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        room_id: Option<i32>,
+    ) -> Result<Vec<SubmissionInfo>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::TasksRead)?;
+
+        let mut select = Submission::find().filter(submission::Column::UserId.eq(auth_user.id));
+        if let Some(room_id) = room_id {
+            select = select.filter(
+                submission::Column::TaskId.in_subquery(
+                    Query::select()
+                        .column(task::Column::Id)
+                        .from(Task)
+                        .and_where(task::Column::RoomId.eq(room_id))
+                        .to_owned(),
+                ),
+            );
+        }
+
+        let submissions: Vec<submission::Model> = select
+            .order_by_desc(submission::Column::SubmittedAt)
+            .all(&my_ctx.db)
+            .await?;
+        Ok(submissions.into_iter().map(SubmissionInfo::from).collect())
+    }
+
+    /// Every submission for `task_id`, oldest first, for the staff of its room.
+    async fn task_submissions(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        task_id: i32,
+    ) -> Result<Vec<SubmissionInfo>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::TasksRead)?;
+        let task = find_task(my_ctx, task_id).await?;
+        require_role(my_ctx, auth_user, task.room_id, STAFF).await?;
+
+        let submissions: Vec<submission::Model> = task
+            .find_related(Submission)
+            .order_by_asc(submission::Column::SubmittedAt)
+            .all(&my_ctx.db)
+            .await?;
+        Ok(submissions.into_iter().map(SubmissionInfo::from).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct SubmissionMutation;
+
+#[Object]
+impl SubmissionMutation {
+    /// Hands in the current user's answer to `task_id`. Submitting again replaces the
+    /// answer, unless it has already been graded.
+    async fn submit_task(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        task_id: i32,
+        body: String,
+        #[graphql(default)] attachments: Vec<String>,
+    ) -> Result<SubmissionInfo, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let task = find_task(my_ctx, task_id).await?;
+        let room = require_role(my_ctx, auth_user, task.room_id, MEMBERS).await?;
+        if !is_visible(&task) && !is_staff(my_ctx, auth_user, task.room_id).await? {
+            return Err(async_graphql::Error::new("task not found".to_string()));
+        }
+        writable(&room)?;
+
+        let attachments = join_attachments(attachments)?;
+        if body.trim().is_empty() && attachments.is_empty() {
+            return Err(async_graphql::Error::new(
+                "a submission needs an answer or an attachment".to_string(),
+            ));
+        }
+
+        let previous: Option<submission::Model> = Submission::find()
+            .filter(submission::Column::TaskId.eq(task_id))
+            .filter(submission::Column::UserId.eq(auth_user.id))
+            .one(&my_ctx.db)
+            .await?;
+
+        let now = Utc::now().naive_utc();
+        let submission: submission::Model = match previous {
+            Some(previous) if previous.status == SubmissionStatus::Graded => {
+                return Err(async_graphql::Error::new(
+                    "this submission has already been graded".to_string(),
+                ))
+            }
+            // The feedback stays so the student can still see what to fix.
+            Some(previous) => {
+                let attempt = previous.attempt + 1;
+                let mut submission: submission::ActiveModel = previous.into();
+                submission.body = Set(body);
+                submission.attachments = Set(attachments);
+                submission.status = Set(SubmissionStatus::Submitted);
+                submission.attempt = Set(attempt);
+                submission.grade = Set(None);
+                submission.graded_by = Set(None);
+                submission.submitted_at = Set(now);
+                submission.graded_at = Set(None);
+                submission.update(&my_ctx.db).await?
+            }
+            None => {
+                submission::ActiveModel {
+                    task_id: Set(task_id),
+                    user_id: Set(auth_user.id),
+                    body: Set(body),
+                    attachments: Set(attachments),
+                    status: Set(SubmissionStatus::Submitted),
+                    attempt: Set(1),
+                    grade: Set(None),
+                    feedback: Set(None),
+                    graded_by: Set(None),
+                    submitted_at: Set(now),
+                    graded_at: Set(None),
+                    ..Default::default()
+                }
+                .insert(&my_ctx.db)
+                .await?
+            }
+        };
+
+        Ok(submission.into())
+    }
+
+    /// Grades submission `submission_id`, or with `return_for_resubmission` sends it back
+    /// so the student can try again. Only the room's owner can do this.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::TasksWrite)")]
+    async fn grade_submission(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        submission_id: i32,
+        grade: Option<i32>,
+        feedback: Option<String>,
+        #[graphql(default)] return_for_resubmission: bool,
+    ) -> Result<SubmissionInfo, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = scoped_user(ctx, Scope::TasksWrite)?;
+
+        let submission: Option<submission::Model> = Submission::find_by_id(submission_id)
+            .one(&my_ctx.db)
+            .await?;
+
+        let submission = match submission {
+            Some(submission) => submission,
+            None => {
+                return Err(async_graphql::Error::new(
+                    "submission not found".to_string(),
+                ))
+            }
+        };
+
+        let task = find_task(my_ctx, submission.task_id).await?;
+        let room = require_role(my_ctx, auth_user, task.room_id, &[MemberRole::Owner]).await?;
+        writable(&room)?;
+
+        let status = if return_for_resubmission {
+            SubmissionStatus::Returned
+        } else {
+            SubmissionStatus::Graded
+        };
+        if status == SubmissionStatus::Graded && grade.is_none() {
+            return Err(async_graphql::Error::new(
+                "a grade is needed unless the submission is returned".to_string(),
+            ));
+        }
+        if grade.is_some_and(|grade| grade < 0) {
+            return Err(async_graphql::Error::new(
+                "the grade must not be negative".to_string(),
+            ));
+        }
+
+        let mut submission: submission::ActiveModel = submission.into();
+        submission.status = Set(status);
+        submission.grade = Set(grade);
+        submission.feedback = Set(feedback);
+        submission.graded_by = Set(Some(auth_user.id));
+        submission.graded_at = Set(Some(Utc::now().naive_utc()));
+        let submission: submission::Model = submission.update(&my_ctx.db).await?;
+
+        let (kind, message) = match status {
+            SubmissionStatus::Returned => (
+                NotificationKind::SubmissionReturned,
+                format!("Your answer to {} was returned for another try", task.title),
+            ),
+            _ => (
+                NotificationKind::SubmissionGraded,
+                format!("Your answer to {} was graded", task.title),
+            ),
+        };
+        my_ctx.events.publish(Event::Notification {
+            user_id: submission.user_id,
+            notification: Notification::new(kind, Some(task.room_id), message),
+        });
+
+        Ok(submission.into())
+    }
+}
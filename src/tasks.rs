@@ -0,0 +1,130 @@
+use async_graphql::{MaybeUndefined, Object};
+use chrono::{NaiveDateTime, Utc};
+use entity::{
+    task::{self, Entity as Task, TaskStatus},
+    user::Role,
+    user_room::MemberRole,
+};
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, EntityTrait, ModelTrait, Set};
+
+use crate::{
+    auth::{scoped_user, RoleGuard},
+    events::Event,
+    membership::require_role,
+    personal_token::Scope,
+    rooms::writable,
+    Context,
+};
+
+/// Tasks students can see: published, and past their publish time if they have one.
+pub fn visible() -> Condition {
+    Condition::all()
+        .add(task::Column::Status.eq(TaskStatus::Published))
+        .add(
+            Condition::any()
+                .add(task::Column::PublishAt.is_null())
+                .add(task::Column::PublishAt.lte(Utc::now().naive_utc())),
+        )
+}
+
+pub fn is_visible(task: &task::Model) -> bool {
+    let now = Utc::now().naive_utc();
+    task.status == TaskStatus::Published
+        && task.publish_at.is_none_or(|publish_at| publish_at <= now)
+}
+
+/// Loads task `task_id` for the owner of its room.
+async fn owned_task(
+    ctx: &async_graphql::Context<'_>,
+    task_id: i32,
+) -> Result<task::Model, async_graphql::Error> {
+    let my_ctx = ctx.data::<Context>().unwrap();
+    let auth_user = scoped_user(ctx, Scope::TasksWrite)?;
+
+    let task: Option<task::Model> = Task::find_by_id(task_id).one(&my_ctx.db).await?;
+
+    let task = match task {
+        Some(task) => task,
+        None => return Err(async_graphql::Error::new("task not found".to_string())),
+    };
+
+    let room = require_role(my_ctx, auth_user, task.room_id, &[MemberRole::Owner]).await?;
+    writable(&room)?;
+    Ok(task)
+}
+
+#[derive(Default)]
+pub struct TaskMutation;
+
+#[Object]
+impl TaskMutation {
+    /// Edits task `task_id`. Fields that are left out stay as they are; `null` clears
+    /// `publishAt` or `dueAt`.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::TasksWrite)")]
+    async fn update_task(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        task_id: i32,
+        title: Option<String>,
+        content: Option<String>,
+        status: Option<TaskStatus>,
+        publish_at: MaybeUndefined<NaiveDateTime>,
+        due_at: MaybeUndefined<NaiveDateTime>,
+    ) -> Result<task::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let task = owned_task(ctx, task_id).await?;
+        let was_visible = is_visible(&task);
+
+        let mut task: task::ActiveModel = task.into();
+
+        if let Some(title) = title {
+            task.title = Set(title);
+        }
+
+        if let Some(content) = content {
+            task.content = Set(content);
+        }
+
+        if let Some(status) = status {
+            task.status = Set(status);
+        }
+
+        match publish_at {
+            MaybeUndefined::Value(publish_at) => task.publish_at = Set(Some(publish_at)),
+            MaybeUndefined::Null => task.publish_at = Set(None),
+            MaybeUndefined::Undefined => {}
+        }
+
+        match due_at {
+            MaybeUndefined::Value(due_at) => task.due_at = Set(Some(due_at)),
+            MaybeUndefined::Null => task.due_at = Set(None),
+            MaybeUndefined::Undefined => {}
+        }
+
+        task.updated_at = Set(Utc::now().naive_utc());
+        let task: task::Model = task.update(&my_ctx.db).await?;
+
+        if !was_visible && is_visible(&task) {
+            my_ctx.events.publish(Event::TaskCreated {
+                room_id: task.room_id,
+                task_id: task.id,
+            });
+        }
+        Ok(task)
+    }
+
+    /// Deletes task `task_id`. Only the room's owner can do this.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher).scope(Scope::TasksWrite)")]
+    async fn delete_task(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        task_id: i32,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let task = owned_task(ctx, task_id).await?;
+
+        task.delete(&my_ctx.db).await?;
+        Ok(true)
+    }
+}
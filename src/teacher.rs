@@ -0,0 +1,256 @@
+use async_graphql::{InputObject, Object};
+use chrono::Utc;
+use entity::{
+    teacher_application::{self, ApplicationStatus, Entity as TeacherApplication},
+    user::{self, Entity as User, Role},
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+
+use crate::{
+    auth::{current_user, RoleGuard},
+    mailer::Email,
+    Context,
+};
+
+/// What a teacher signup has to tell admins besides the account itself.
+#[derive(InputObject)]
+pub struct TeacherApplicationInput {
+    pub subject: String,
+    pub proof_note: String,
+}
+
+/// Files a pending application for `user` to become a teacher at `school`.
+pub async fn submit(
+    my_ctx: &Context,
+    user: &user::Model,
+    school: String,
+    input: TeacherApplicationInput,
+) -> Result<teacher_application::Model, async_graphql::Error> {
+    let pending: Option<teacher_application::Model> = TeacherApplication::find()
+        .filter(teacher_application::Column::UserId.eq(user.id))
+        .filter(teacher_application::Column::Status.eq(ApplicationStatus::Pending))
+        .one(&my_ctx.db)
+        .await?;
+    if pending.is_some() {
+        return Err(async_graphql::Error::new(
+            "you already have a pending application".to_string(),
+        ));
+    }
+
+    let application = teacher_application::ActiveModel {
+        user_id: Set(user.id),
+        school: Set(school),
+        subject: Set(input.subject),
+        proof_note: Set(input.proof_note),
+        status: Set(ApplicationStatus::Pending),
+        reviewed_by: Set(None),
+        review_note: Set(None),
+        created_at: Set(Utc::now().naive_utc()),
+        reviewed_at: Set(None),
+        ..Default::default()
+    }
+    .insert(&my_ctx.db)
+    .await?;
+
+    Ok(application)
+}
+
+/// Moves a pending application to `status`, failing if it was already reviewed.
+async fn review(
+    ctx: &async_graphql::Context<'_>,
+    application_id: i32,
+    status: ApplicationStatus,
+    note: Option<String>,
+) -> Result<teacher_application::Model, async_graphql::Error> {
+    let my_ctx = ctx.data::<Context>().unwrap();
+    let auth_user = current_user(ctx)?;
+
+    let reviewed = TeacherApplication::update_many()
+        .col_expr(teacher_application::Column::Status, Expr::value(status))
+        .col_expr(
+            teacher_application::Column::ReviewedBy,
+            Expr::value(auth_user.id),
+        )
+        .col_expr(teacher_application::Column::ReviewNote, Expr::value(note))
+        .col_expr(
+            teacher_application::Column::ReviewedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(teacher_application::Column::Id.eq(application_id))
+        .filter(teacher_application::Column::Status.eq(ApplicationStatus::Pending))
+        .exec(&my_ctx.db)
+        .await?;
+
+    if reviewed.rows_affected == 0 {
+        return Err(async_graphql::Error::new(
+            "there is no pending application with this id".to_string(),
+        ));
+    }
+
+    let application: Option<teacher_application::Model> =
+        TeacherApplication::find_by_id(application_id)
+            .one(&my_ctx.db)
+            .await?;
+
+    match application {
+        Some(application) => Ok(application),
+        None => Err(async_graphql::Error::new(
+            "there is no pending application with this id".to_string(),
+        )),
+    }
+}
+
+/// Tells the applicant how their application went. A lost mail does not undo the review.
+async fn notify(my_ctx: &Context, application: &teacher_application::Model) {
+    let user: Option<user::Model> =
+        match User::find_by_id(application.user_id).one(&my_ctx.db).await {
+            Ok(user) => user,
+            Err(err) => {
+                tracing::error!("could not load applicant: {}", err);
+                return;
+            }
+        };
+    let user = match user {
+        Some(user) => user,
+        None => return,
+    };
+
+    let (subject, mut body) = match application.status {
+        ApplicationStatus::Approved => (
+            "Your teacher account was approved",
+            "Your application was approved. Log in again to start creating rooms and tasks."
+                .to_string(),
+        ),
+        _ => (
+            "Your teacher application was rejected",
+            "Your application to become a teacher was rejected.".to_string(),
+        ),
+    };
+    if let Some(note) = &application.review_note {
+        body = format!("{}\n\n{}", body, note);
+    }
+
+    let email = Email {
+        to: user.email,
+        subject: subject.to_string(),
+        body,
+    };
+    if let Err(err) = my_ctx.mailer.send(email).await {
+        tracing::error!("could not send application result: {}", err);
+    }
+}
+
+#[derive(Default)]
+pub struct TeacherQuery;
+
+#[Object]
+impl TeacherQuery {
+    /// The current user's latest teacher application, if any.
+    async fn my_teacher_application(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<Option<teacher_application::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let application: Option<teacher_application::Model> = TeacherApplication::find()
+            .filter(teacher_application::Column::UserId.eq(auth_user.id))
+            .order_by_desc(teacher_application::Column::CreatedAt)
+            .one(&my_ctx.db)
+            .await?;
+        Ok(application)
+    }
+
+    /// Teacher applications in `status`, oldest first.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn teacher_applications(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default_with = "ApplicationStatus::Pending")] status: ApplicationStatus,
+    ) -> Result<Vec<teacher_application::Model>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+
+        let applications: Vec<teacher_application::Model> = TeacherApplication::find()
+            .filter(teacher_application::Column::Status.eq(status))
+            .order_by_asc(teacher_application::Column::CreatedAt)
+            .all(&my_ctx.db)
+            .await?;
+        Ok(applications)
+    }
+}
+
+#[derive(Default)]
+pub struct TeacherMutation;
+
+#[Object]
+impl TeacherMutation {
+    /// Lets a student ask to become a teacher, e.g. after an earlier rejection.
+    async fn apply_for_teacher(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        school: String,
+        application: TeacherApplicationInput,
+    ) -> Result<teacher_application::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let user: Option<user::Model> = User::find_by_id(auth_user.id).one(&my_ctx.db).await?;
+
+        let user = match user {
+            Some(user) if user.role == Role::Student => user,
+            Some(_) => {
+                return Err(async_graphql::Error::new(
+                    "you are already a teacher".to_string(),
+                ))
+            }
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        submit(my_ctx, &user, school, application).await
+    }
+
+    /// Makes the applicant a teacher. Their role changes with their next token refresh.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn approve_teacher(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        application_id: i32,
+        note: Option<String>,
+    ) -> Result<teacher_application::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let application = review(ctx, application_id, ApplicationStatus::Approved, note).await?;
+
+        // Never demote an admin who happened to apply.
+        User::update_many()
+            .col_expr(user::Column::Role, Expr::value(Role::Teacher))
+            .col_expr(
+                user::Column::School,
+                Expr::value(application.school.clone()),
+            )
+            .col_expr(user::Column::UpdatedAt, Expr::value(Utc::now().naive_utc()))
+            .filter(user::Column::Id.eq(application.user_id))
+            .filter(user::Column::Role.eq(Role::Student))
+            .exec(&my_ctx.db)
+            .await?;
+
+        notify(my_ctx, &application).await;
+        Ok(application)
+    }
+
+    /// Turns the application down; `reason` is mailed to the applicant.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn reject_teacher(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        application_id: i32,
+        reason: Option<String>,
+    ) -> Result<teacher_application::Model, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let application = review(ctx, application_id, ApplicationStatus::Rejected, reason).await?;
+
+        notify(my_ctx, &application).await;
+        Ok(application)
+    }
+}
@@ -0,0 +1,437 @@
+use async_graphql::{Object, SimpleObject};
+use chrono::{Duration, NaiveDateTime, Utc};
+use entity::{
+    login_attempt::{self, Entity as LoginAttempt},
+    user::Role,
+};
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    auth::{ClientInfo, RoleGuard},
+    Context,
+};
+
+/// Failures allowed per account before it gets locked.
+const ACCOUNT_LIMIT: i32 = 5;
+/// Failures allowed per client address; higher since schools share addresses.
+const IP_LIMIT: i32 = 50;
+/// First lockout in seconds; it doubles with every further failure.
+const BASE_LOCKOUT: i64 = 30;
+const MAX_LOCKOUT: i64 = 60 * 60;
+/// Counters start over once nothing has failed for this many minutes.
+const RESET_AFTER: i64 = 60;
+
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(name = "LoginAttempts")]
+pub struct Attempts {
+    pub key: String,
+    pub failed_login_attempts: i32,
+    pub last_failed_at: NaiveDateTime,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+impl Attempts {
+    fn is_locked(&self, now: NaiveDateTime) -> bool {
+        self.locked_until.is_some_and(|until| until > now)
+    }
+
+    /// Counts one more failure on top of `prev`, locking once `limit` is reached.
+    fn next(key: &str, prev: Option<Attempts>, limit: i32, now: NaiveDateTime) -> Attempts {
+        let failed = match prev {
+            Some(prev) if prev.last_failed_at + Duration::minutes(RESET_AFTER) > now => {
+                prev.failed_login_attempts + 1
+            }
+            _ => 1,
+        };
+
+        let locked_until = if failed >= limit {
+            let doublings = (failed - limit).min(16) as u32;
+            let seconds = (BASE_LOCKOUT << doublings).min(MAX_LOCKOUT);
+            Some(now + Duration::seconds(seconds))
+        } else {
+            None
+        };
+
+        Attempts {
+            key: key.to_string(),
+            failed_login_attempts: failed,
+            last_failed_at: now,
+            locked_until,
+        }
+    }
+}
+
+impl From<login_attempt::Model> for Attempts {
+    fn from(model: login_attempt::Model) -> Self {
+        Self {
+            key: model.key,
+            failed_login_attempts: model.failed_attempts,
+            last_failed_at: model.last_failed_at,
+            locked_until: model.locked_until,
+        }
+    }
+}
+
+/// Where failed login counters live.
+#[async_trait::async_trait]
+pub trait AttemptStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<Attempts>, DbErr>;
+    async fn record_failure(&self, key: &str, limit: i32) -> Result<Attempts, DbErr>;
+    async fn clear(&self, key: &str) -> Result<(), DbErr>;
+    async fn all(&self) -> Result<Vec<Attempts>, DbErr>;
+}
+
+/// Keeps counters in the `login_attempt` table so every replica sees them.
+pub struct PostgresStore {
+    db: DatabaseConnection,
+}
+
+impl PostgresStore {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait::async_trait]
+impl AttemptStore for PostgresStore {
+    async fn get(&self, key: &str) -> Result<Option<Attempts>, DbErr> {
+        let row = LoginAttempt::find_by_id(key.to_string())
+            .one(&self.db)
+            .await?;
+        Ok(row.map(Attempts::from))
+    }
+
+    async fn record_failure(&self, key: &str, limit: i32) -> Result<Attempts, DbErr> {
+        let now = Utc::now().naive_utc();
+        let txn = self.db.begin().await?;
+
+        // Make sure a row exists so concurrent failures serialize on its lock.
+        LoginAttempt::insert(login_attempt::ActiveModel {
+            key: Set(key.to_string()),
+            failed_attempts: Set(0),
+            last_failed_at: Set(now),
+            locked_until: Set(None),
+        })
+        .on_conflict(
+            OnConflict::column(login_attempt::Column::Key)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&txn)
+        .await?;
+
+        let prev = LoginAttempt::find_by_id(key.to_string())
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .map(Attempts::from);
+        let next = Attempts::next(key, prev, limit, now);
+
+        login_attempt::ActiveModel {
+            key: Set(key.to_string()),
+            failed_attempts: Set(next.failed_login_attempts),
+            last_failed_at: Set(next.last_failed_at),
+            locked_until: Set(next.locked_until),
+        }
+        .update(&txn)
+        .await?;
+
+        txn.commit().await?;
+        Ok(next)
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), DbErr> {
+        LoginAttempt::delete_by_id(key.to_string())
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn all(&self) -> Result<Vec<Attempts>, DbErr> {
+        let rows = LoginAttempt::find()
+            .filter(login_attempt::Column::FailedAttempts.gt(0))
+            .order_by_desc(login_attempt::Column::LastFailedAt)
+            .all(&self.db)
+            .await?;
+        Ok(rows.into_iter().map(Attempts::from).collect())
+    }
+}
+
+/// Keeps counters in process memory; only suitable for a single node.
+#[derive(Default)]
+pub struct MemoryStore {
+    attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+#[async_trait::async_trait]
+impl AttemptStore for MemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<Attempts>, DbErr> {
+        Ok(self.attempts.lock().unwrap().get(key).cloned())
+    }
+
+    async fn record_failure(&self, key: &str, limit: i32) -> Result<Attempts, DbErr> {
+        let now = Utc::now().naive_utc();
+        let mut attempts = self.attempts.lock().unwrap();
+        // Drop counters that would start over anyway, so the map only holds recent failures.
+        attempts.retain(|_, prev| prev.last_failed_at + Duration::minutes(RESET_AFTER) > now);
+        let next = Attempts::next(key, attempts.remove(key), limit, now);
+        attempts.insert(key.to_string(), next.clone());
+        Ok(next)
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), DbErr> {
+        self.attempts.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    async fn all(&self) -> Result<Vec<Attempts>, DbErr> {
+        let mut all: Vec<Attempts> = self.attempts.lock().unwrap().values().cloned().collect();
+        all.sort_by_key(|attempts| std::cmp::Reverse(attempts.last_failed_at));
+        Ok(all)
+    }
+}
+
+/// Uses the in-process store when `LOGIN_ATTEMPT_STORE=memory`, Postgres otherwise.
+pub fn from_env(db: DatabaseConnection) -> Arc<dyn AttemptStore> {
+    match dotenvy::var("LOGIN_ATTEMPT_STORE").as_deref() {
+        Ok("memory") => Arc::new(MemoryStore::default()),
+        _ => Arc::new(PostgresStore::new(db)),
+    }
+}
+
+fn account_key(email: &str) -> String {
+    format!("email:{}", email.to_lowercase())
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+fn keys(email: &str, client: &ClientInfo) -> Vec<(String, i32)> {
+    let mut keys = vec![(account_key(email), ACCOUNT_LIMIT)];
+    if let Some(ip) = &client.ip {
+        keys.push((ip_key(ip), IP_LIMIT));
+    }
+    keys
+}
+
+/// Fails if the account or the client address is currently locked out.
+pub async fn check(
+    store: &dyn AttemptStore,
+    email: &str,
+    client: &ClientInfo,
+) -> Result<(), async_graphql::Error> {
+    let now = Utc::now().naive_utc();
+    for (key, _) in keys(email, client) {
+        if let Some(attempts) = store.get(&key).await? {
+            if attempts.is_locked(now) {
+                return Err(async_graphql::Error::new(
+                    "too many failed attempts, try again later".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn record_failure(
+    store: &dyn AttemptStore,
+    email: &str,
+    client: &ClientInfo,
+) -> Result<(), DbErr> {
+    for (key, limit) in keys(email, client) {
+        store.record_failure(&key, limit).await?;
+    }
+    Ok(())
+}
+
+/// Forgets the account's failures after a successful login. The address keeps its
+/// count, so one good password does not unlock guessing at other accounts.
+pub async fn record_success(store: &dyn AttemptStore, email: &str) -> Result<(), DbErr> {
+    store.clear(&account_key(email)).await
+}
+
+#[derive(Default)]
+pub struct ThrottleQuery;
+
+#[Object]
+impl ThrottleQuery {
+    /// Accounts and addresses with recent failed logins.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn login_attempts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        #[graphql(default)] locked_only: bool,
+    ) -> Result<Vec<Attempts>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let now = Utc::now().naive_utc();
+
+        let mut all = my_ctx.attempts.all().await?;
+        if locked_only {
+            all.retain(|attempts| attempts.is_locked(now));
+        }
+        Ok(all)
+    }
+}
+
+#[derive(Default)]
+pub struct ThrottleMutation;
+
+#[Object]
+impl ThrottleMutation {
+    /// Lifts the lockout on a key from `loginAttempts`.
+    #[graphql(guard = "RoleGuard::new(Role::Admin)")]
+    async fn clear_login_attempts(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        key: String,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        my_ctx.attempts.clear(&key).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(ip: &str) -> ClientInfo {
+        ClientInfo {
+            user_agent: None,
+            ip: Some(ip.to_string()),
+        }
+    }
+
+    /// Applies `failures` failures to a fresh counter, one second apart.
+    fn after(failures: i32, start: NaiveDateTime) -> Attempts {
+        let mut attempts = None;
+        for i in 0..failures {
+            let now = start + Duration::seconds(i as i64);
+            attempts = Some(Attempts::next("email:a", attempts, ACCOUNT_LIMIT, now));
+        }
+        attempts.unwrap()
+    }
+
+    fn lockout(attempts: &Attempts) -> Option<i64> {
+        attempts
+            .locked_until
+            .map(|until| (until - attempts.last_failed_at).num_seconds())
+    }
+
+    #[test]
+    fn locks_at_the_limit() {
+        let now = Utc::now().naive_utc();
+        let below = after(ACCOUNT_LIMIT - 1, now);
+        assert_eq!(lockout(&below), None);
+        assert!(!below.is_locked(below.last_failed_at));
+
+        let at = after(ACCOUNT_LIMIT, now);
+        assert_eq!(lockout(&at), Some(BASE_LOCKOUT));
+        assert!(at.is_locked(at.last_failed_at));
+        assert!(!at.is_locked(at.last_failed_at + Duration::seconds(BASE_LOCKOUT)));
+    }
+
+    #[test]
+    fn lockout_doubles_up_to_the_maximum() {
+        let now = Utc::now().naive_utc();
+        assert_eq!(
+            lockout(&after(ACCOUNT_LIMIT + 1, now)),
+            Some(2 * BASE_LOCKOUT)
+        );
+        assert_eq!(
+            lockout(&after(ACCOUNT_LIMIT + 2, now)),
+            Some(4 * BASE_LOCKOUT)
+        );
+        assert_eq!(lockout(&after(ACCOUNT_LIMIT + 30, now)), Some(MAX_LOCKOUT));
+    }
+
+    #[test]
+    fn old_failures_are_forgotten() {
+        let prev = after(ACCOUNT_LIMIT, Utc::now().naive_utc());
+        let later = prev.last_failed_at + Duration::minutes(RESET_AFTER);
+        let next = Attempts::next("email:a", Some(prev), ACCOUNT_LIMIT, later);
+        assert_eq!(next.failed_login_attempts, 1);
+        assert_eq!(next.locked_until, None);
+    }
+
+    #[tokio::test]
+    async fn success_clears_the_account() {
+        let store = MemoryStore::default();
+        for _ in 0..ACCOUNT_LIMIT {
+            record_failure(&store, "a@example.com", &client("10.0.0.1"))
+                .await
+                .unwrap();
+        }
+        assert!(check(&store, "A@example.com", &client("10.0.0.1"))
+            .await
+            .is_err());
+
+        record_success(&store, "a@example.com").await.unwrap();
+        assert!(check(&store, "a@example.com", &client("10.0.0.1"))
+            .await
+            .is_ok());
+        // The address keeps its count.
+        let ip = store.get(&ip_key("10.0.0.1")).await.unwrap().unwrap();
+        assert_eq!(ip.failed_login_attempts, ACCOUNT_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn memory_store_forgets_old_counters() {
+        let store = MemoryStore::default();
+        let stale = after(
+            2,
+            Utc::now().naive_utc() - Duration::minutes(RESET_AFTER + 1),
+        );
+        store
+            .attempts
+            .lock()
+            .unwrap()
+            .insert(stale.key.clone(), stale);
+
+        store
+            .record_failure("email:b", ACCOUNT_LIMIT)
+            .await
+            .unwrap();
+        assert!(store.get("email:a").await.unwrap().is_none());
+        assert!(store.get("email:b").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn accounts_and_addresses_are_counted_separately() {
+        let store = MemoryStore::default();
+        for _ in 0..ACCOUNT_LIMIT {
+            record_failure(&store, "a@example.com", &client("10.0.0.1"))
+                .await
+                .unwrap();
+        }
+        // The locked account stays locked from anywhere; others on the address do not.
+        assert!(check(&store, "a@example.com", &client("10.0.0.2"))
+            .await
+            .is_err());
+        assert!(check(&store, "b@example.com", &client("10.0.0.1"))
+            .await
+            .is_ok());
+
+        // Guessing at many accounts from one address locks the address instead.
+        for i in ACCOUNT_LIMIT..IP_LIMIT {
+            let email = format!("user{}@example.com", i);
+            record_failure(&store, &email, &client("10.0.0.1"))
+                .await
+                .unwrap();
+        }
+        assert!(check(&store, "c@example.com", &client("10.0.0.1"))
+            .await
+            .is_err());
+        assert!(check(&store, "c@example.com", &client("10.0.0.2"))
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,427 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use async_graphql::{Object, SimpleObject};
+use chrono::Utc;
+use constant_time_eq::constant_time_eq;
+use entity::{
+    recovery_code::{self, Entity as RecoveryCode},
+    user::{self, Entity as User, Role},
+    user_token::TokenKind,
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, EntityTrait, QueryFilter, Set,
+};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::{
+    account,
+    auth::{current_user, hash_token, random_token, ClientInfo, RoleGuard},
+    session, throttle, Context, LoginResponse,
+};
+
+/// Lifetime of the challenge token handed out between the two login steps, in minutes.
+const CHALLENGE_EXPIRATION: i64 = 5;
+const RECOVERY_CODES: usize = 10;
+
+#[derive(SimpleObject)]
+pub struct TotpSetup {
+    /// Base32 secret for apps that cannot scan `otpauth_uri`.
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Returned by `login` instead of tokens when the account has two-factor enabled.
+#[derive(SimpleObject)]
+pub struct TotpChallenge {
+    /// Pass to `loginTotp` together with a code.
+    pub challenge_token: String,
+}
+
+fn totp(my_ctx: &Context, secret: &str, email: &str) -> Result<TOTP, async_graphql::Error> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|err| async_graphql::Error::new(format!("{:?}", err)))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        Some(my_ctx.config.totp_issuer.clone()),
+        email.to_string(),
+    )
+    .map_err(|err| async_graphql::Error::new(err.to_string()))
+}
+
+/// The time step `code` belongs to at `time`, if it is valid then and newer than
+/// `last_step`.
+fn code_step(totp: &TOTP, code: &str, time: u64, last_step: Option<i64>) -> Option<i64> {
+    let code = code.trim();
+    let current = (time / totp.step) as i64;
+    let skew = totp.skew as i64;
+    (current - skew..=current + skew)
+        .filter(|step| last_step.is_none_or(|last| *step > last))
+        .find(|step| {
+            constant_time_eq(
+                totp.generate(*step as u64 * totp.step).as_bytes(),
+                code.as_bytes(),
+            )
+        })
+}
+
+/// Accepts a TOTP `code` of `user` at most once: the step it belongs to is stored, and
+/// codes from that step or earlier are refused from then on.
+async fn use_totp_code(
+    my_ctx: &Context,
+    user: &user::Model,
+    secret: &str,
+    code: &str,
+) -> Result<bool, async_graphql::Error> {
+    let totp = totp(my_ctx, secret, &user.email)?;
+    let now = Utc::now().timestamp() as u64;
+    let step = match code_step(&totp, code, now, user.totp_last_step) {
+        Some(step) => step,
+        None => return Ok(false),
+    };
+
+    // Only one of two requests racing with the same code gets to move the step on.
+    let claimed = User::update_many()
+        .col_expr(user::Column::TotpLastStep, Expr::value(step))
+        .filter(user::Column::Id.eq(user.id))
+        .filter(
+            Condition::any()
+                .add(user::Column::TotpLastStep.is_null())
+                .add(user::Column::TotpLastStep.lt(step)),
+        )
+        .exec(&my_ctx.db)
+        .await?;
+    Ok(claimed.rows_affected > 0)
+}
+
+/// Recovery codes are compared without dashes and case.
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().replace('-', "").to_lowercase()
+}
+
+/// Uses up one of `user_id`'s recovery codes if `code` is among them.
+async fn use_recovery_code(
+    my_ctx: &Context,
+    user_id: i32,
+    code: &str,
+) -> Result<bool, async_graphql::Error> {
+    let used = RecoveryCode::update_many()
+        .col_expr(
+            recovery_code::Column::UsedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(recovery_code::Column::UserId.eq(user_id))
+        .filter(
+            recovery_code::Column::CodeHash
+                .eq(hash_token(&my_ctx.refr_key, &normalize_recovery_code(code))),
+        )
+        .filter(recovery_code::Column::UsedAt.is_null())
+        .exec(&my_ctx.db)
+        .await?;
+    Ok(used.rows_affected > 0)
+}
+
+/// Replaces all recovery codes of `user_id` and returns the new ones in plaintext.
+async fn regenerate_recovery_codes(
+    my_ctx: &Context,
+    user_id: i32,
+) -> Result<Vec<String>, async_graphql::Error> {
+    RecoveryCode::delete_many()
+        .filter(recovery_code::Column::UserId.eq(user_id))
+        .exec(&my_ctx.db)
+        .await?;
+
+    let now = Utc::now().naive_utc();
+    let mut codes = Vec::with_capacity(RECOVERY_CODES);
+    for _ in 0..RECOVERY_CODES {
+        let raw = &random_token()[..16];
+        let code = format!(
+            "{}-{}-{}-{}",
+            &raw[..4],
+            &raw[4..8],
+            &raw[8..12],
+            &raw[12..]
+        );
+        recovery_code::ActiveModel {
+            user_id: Set(user_id),
+            code_hash: Set(hash_token(&my_ctx.refr_key, raw)),
+            created_at: Set(now),
+            used_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&my_ctx.db)
+        .await?;
+        codes.push(code);
+    }
+    Ok(codes)
+}
+
+/// Starts the second login step for `user`.
+pub async fn challenge(
+    my_ctx: &Context,
+    user: &user::Model,
+) -> Result<TotpChallenge, async_graphql::Error> {
+    let challenge_token = account::issue_token(
+        &my_ctx.db,
+        &my_ctx.refr_key,
+        user.id,
+        TokenKind::LoginChallenge,
+        None,
+        CHALLENGE_EXPIRATION,
+    )
+    .await?;
+    Ok(TotpChallenge { challenge_token })
+}
+
+#[derive(Default)]
+pub struct TotpMutation;
+
+#[Object]
+impl TotpMutation {
+    /// Generates a TOTP secret for the current user. Two-factor login only turns on
+    /// after a code from it is passed to `confirmTotp`.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher)")]
+    async fn enable_totp(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> Result<TotpSetup, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let user: Option<user::Model> = User::find_by_id(auth_user.id).one(&my_ctx.db).await?;
+
+        let user = match user {
+            Some(user) => user,
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        if user.totp_enabled_at.is_some() {
+            return Err(async_graphql::Error::new(
+                "two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let mut bytes = [0u8; 20];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = match Secret::Raw(bytes.to_vec()).to_encoded() {
+            Secret::Encoded(secret) => secret,
+            Secret::Raw(_) => unreachable!(),
+        };
+        let otpauth_uri = totp(my_ctx, &secret, &user.email)?.get_url();
+
+        user::ActiveModel {
+            id: Set(user.id),
+            totp_secret: Set(Some(secret.clone())),
+            updated_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .update(&my_ctx.db)
+        .await?;
+
+        Ok(TotpSetup {
+            secret,
+            otpauth_uri,
+        })
+    }
+
+    /// Turns on two-factor login and returns recovery codes. They are only shown once.
+    #[graphql(guard = "RoleGuard::new(Role::Teacher)")]
+    async fn confirm_totp(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        code: String,
+    ) -> Result<Vec<String>, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let auth_user = current_user(ctx)?;
+
+        let user: Option<user::Model> = User::find_by_id(auth_user.id).one(&my_ctx.db).await?;
+
+        let (user, secret) = match user {
+            Some(user) if user.totp_enabled_at.is_none() => match user.totp_secret.clone() {
+                Some(secret) => (user, secret),
+                None => {
+                    return Err(async_graphql::Error::new(
+                        "call enableTotp first".to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(async_graphql::Error::new(
+                    "two-factor authentication is already enabled".to_string(),
+                ))
+            }
+        };
+
+        if !use_totp_code(my_ctx, &user, &secret, &code).await? {
+            return Err(async_graphql::Error::new("Wrong code".to_string()));
+        }
+
+        user::ActiveModel {
+            id: Set(user.id),
+            totp_enabled_at: Set(Some(Utc::now().naive_utc())),
+            updated_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .update(&my_ctx.db)
+        .await?;
+
+        regenerate_recovery_codes(my_ctx, user.id).await
+    }
+
+    /// Turns off two-factor login for the current user. Once it is on, this takes a TOTP
+    /// or recovery code as well as the password, so one factor cannot remove the other.
+    async fn disable_totp(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        password: String,
+        code: Option<String>,
+    ) -> Result<bool, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let client = ctx.data::<ClientInfo>()?;
+        let user = account::reauthenticate(ctx, &password).await?;
+
+        if let (Some(secret), Some(_)) = (user.totp_secret.clone(), user.totp_enabled_at) {
+            throttle::check(my_ctx.attempts.as_ref(), &user.email, client).await?;
+
+            let code = code.unwrap_or_default();
+            let valid = use_totp_code(my_ctx, &user, &secret, &code).await?
+                || use_recovery_code(my_ctx, user.id, &code).await?;
+            if !valid {
+                throttle::record_failure(my_ctx.attempts.as_ref(), &user.email, client).await?;
+                return Err(async_graphql::Error::new("Wrong code".to_string()));
+            }
+        }
+
+        user::ActiveModel {
+            id: Set(user.id),
+            totp_secret: Set(None),
+            totp_enabled_at: Set(None),
+            totp_last_step: Set(None),
+            updated_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .update(&my_ctx.db)
+        .await?;
+
+        RecoveryCode::delete_many()
+            .filter(recovery_code::Column::UserId.eq(user.id))
+            .exec(&my_ctx.db)
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Second login step: trades a challenge from `login` and a TOTP or recovery code
+    /// for tokens.
+    async fn login_totp(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        challenge_token: String,
+        code: String,
+    ) -> Result<LoginResponse, async_graphql::Error> {
+        let my_ctx = ctx.data::<Context>().unwrap();
+        let client = ctx.data::<ClientInfo>()?;
+
+        let challenge = account::find_token(
+            &my_ctx.db,
+            &my_ctx.refr_key,
+            &challenge_token,
+            TokenKind::LoginChallenge,
+        )
+        .await?;
+
+        let user: Option<user::Model> = User::find_by_id(challenge.user_id).one(&my_ctx.db).await?;
+
+        let (user, secret) = match user {
+            Some(user) => match (user.totp_secret.clone(), user.totp_enabled_at) {
+                (Some(secret), Some(_)) => (user, secret),
+                _ => return Err(async_graphql::Error::new("Wrong token".to_string())),
+            },
+            None => return Err(async_graphql::Error::new("Wrong token".to_string())),
+        };
+
+        throttle::check(my_ctx.attempts.as_ref(), &user.email, client).await?;
+
+        let valid = use_totp_code(my_ctx, &user, &secret, &code).await?
+            || use_recovery_code(my_ctx, user.id, &code).await?;
+
+        if !valid {
+            throttle::record_failure(my_ctx.attempts.as_ref(), &user.email, client).await?;
+            return Err(async_graphql::Error::new("Wrong code".to_string()));
+        }
+
+        account::use_token(&my_ctx.db, &challenge).await?;
+        throttle::record_success(my_ctx.attempts.as_ref(), &user.email).await?;
+
+        let refresh_token = session::start(&my_ctx.db, &my_ctx.refr_key, user.id, client).await?;
+        my_ctx.issue_tokens(&user, refresh_token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME: u64 = 1_700_000_000;
+
+    fn test_totp() -> TOTP {
+        TOTP::new(
+            Algorithm::SHA1,
+            6,
+            1,
+            30,
+            b"12345678901234567890".to_vec(),
+            None,
+            "ada@example.com".to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn accepts_codes_within_the_skew() {
+        let totp = test_totp();
+        let step = (TIME / 30) as i64;
+        assert_eq!(
+            code_step(&totp, &totp.generate(TIME), TIME, None),
+            Some(step)
+        );
+        assert_eq!(
+            code_step(&totp, &totp.generate(TIME - 30), TIME, None),
+            Some(step - 1)
+        );
+        assert_eq!(
+            code_step(&totp, &totp.generate(TIME + 30), TIME, None),
+            Some(step + 1)
+        );
+        assert_eq!(
+            code_step(&totp, &totp.generate(TIME - 60), TIME, None),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_replayed_codes() {
+        let totp = test_totp();
+        let step = (TIME / 30) as i64;
+        let code = totp.generate(TIME);
+        assert_eq!(code_step(&totp, &code, TIME, None), Some(step));
+
+        // Neither in the same step nor while the skew would still admit it.
+        assert_eq!(code_step(&totp, &code, TIME, Some(step)), None);
+        assert_eq!(code_step(&totp, &code, TIME + 30, Some(step)), None);
+        // An older code does not work either.
+        assert_eq!(
+            code_step(&totp, &totp.generate(TIME - 30), TIME, Some(step)),
+            None
+        );
+
+        let next = totp.generate(TIME + 30);
+        assert_eq!(
+            code_step(&totp, &next, TIME + 30, Some(step)),
+            Some(step + 1)
+        );
+    }
+}